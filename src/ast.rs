@@ -0,0 +1,867 @@
+use crate::{
+    lexer::Comment,
+    string_utils::print_error,
+    token::{Token, TokenTypes},
+};
+
+// Where a node sits in the source, from the start of its first token
+// to the start of its last one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line_number: usize,
+    pub row: usize,
+    pub end_line_number: usize,
+    pub end_row: usize,
+}
+
+impl Span {
+    fn of(token: &Token) -> Self {
+        Span {
+            line_number: token.line_number,
+            row: token.row,
+            end_line_number: token.line_number,
+            end_row: token.row,
+        }
+    }
+
+    fn to(self, end: Span) -> Self {
+        Span {
+            end_line_number: end.end_line_number,
+            end_row: end.end_row,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Interpolation(Vec<Statement>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(String),
+    // Strings, blocks and lists keep the token they were written as, the
+    // vm takes them as they are and parses a block's body when it runs
+    String {
+        parts: Vec<StringPart>,
+        token: Token,
+    },
+    Bool(bool),
+    Nothing,
+    // a.b.c, read straight away when written with a trailing colon
+    Name {
+        path: Vec<String>,
+        now: bool,
+    },
+    // f(a b) or a keyword taking everything up to the next ;
+    Call {
+        name: Vec<String>,
+        builtin: bool,
+        args: Vec<Node>,
+        parens: bool,
+        // How many arguments come before each comma, f(a, b) calls f
+        // once for every comma and once at the end
+        commas: Vec<usize>,
+    },
+    // Field access on something that isn't a plain name, {x: = 1;}.x:
+    Field {
+        target: Box<Node>,
+        path: Vec<String>,
+        now: bool,
+    },
+    Unary {
+        op: String,
+        operand: Box<Node>,
+    },
+    Binary {
+        op: String,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    Assign {
+        target: Box<Node>,
+        value: Box<Node>,
+    },
+    Block {
+        params: Vec<String>,
+        body: Vec<Statement>,
+        token: Token,
+        // # comments after the last statement
+        end_comments: Vec<String>,
+    },
+    List {
+        items: Vec<Node>,
+        token: Token,
+    },
+    Group(Box<Node>),
+    // @{...}
+    Run(Box<Node>),
+    // Symbols with no structure of their own, like ~ outside a block head
+    Symbol(String),
+    // Tokens the vm built itself and hands back as they are, like the
+    // break a block starts with or a set written into an object
+    Value(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub expr: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub node: Node,
+    pub semicolon: bool,
+    // # comments on the lines before it and at the end of its last line,
+    // only filled in for --fmt
+    pub comments: Vec<String>,
+    pub trailing: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub line_number: usize,
+    pub row: usize,
+}
+
+// Builds the syntax tree of a lexed program
+pub fn parse(tokens: &[Token]) -> Result<Vec<Statement>, ParseError> {
+    Cursor { tokens, pos: 0 }.sequence()
+}
+
+// Builds the syntax tree of a whole file. A file that does not parse
+// is reported and stops the program, so none of it runs
+pub fn parse_file(tokens: &[Token], file: &str) -> Vec<Statement> {
+    parse(tokens).unwrap_or_else(|e| {
+        print_error(&e.message, e.line_number, e.row, file, "");
+        vec![]
+    })
+}
+
+// Operators that go between two values
+fn binary_precedence(token: &Token) -> Option<usize> {
+    match token.value.as_str() {
+        "+" | "-" | "*" | "/" | "%" | "//" | "^" | "<" | ">" | "<=" | ">=" | "==" | "!=" | "&&"
+        | "||"
+            if token.token_type == TokenTypes::Symbol =>
+        {
+            Some(token.precedence())
+        }
+        _ => None,
+    }
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn at(&self, symbol: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.token_type == TokenTypes::Symbol && t.value == symbol)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.at(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn error<T>(&self, message: String, token: Option<&Token>) -> Result<T, ParseError> {
+        let token = token.or_else(|| self.tokens.last());
+        Err(ParseError {
+            message,
+            line_number: token.map_or(0, |t| t.line_number),
+            row: token.map_or(0, |t| t.row),
+        })
+    }
+
+    // Statements up to the end of the tokens
+    fn sequence(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut statements: Vec<Statement> = vec![];
+        while self.peek().is_some() {
+            // Extra semicolons don't change anything
+            if self.eat(";") {
+                if let Some(last) = statements.last_mut() {
+                    last.semicolon = true;
+                }
+                continue;
+            }
+            // Nor does a closing bracket with nothing open
+            if self.eat(")") {
+                continue;
+            }
+            // The break a block starts with and the ~ after its parameters
+            // stand on their own, a sign after them starts a new value
+            if let Some(token) = self.peek().filter(|t| {
+                t.token_type == TokenTypes::Break
+                    || (t.token_type == TokenTypes::Symbol && (t.value == "~" || t.value == "?"))
+            }) {
+                self.pos += 1;
+                let expr = match token.token_type {
+                    TokenTypes::Break => Expr::Value(token.clone()),
+                    _ => Expr::Symbol(token.value.clone()),
+                };
+                let node = Node {
+                    expr,
+                    span: Span::of(token),
+                };
+                let semicolon = self.eat(";");
+                statements.push(Statement {
+                    node,
+                    semicolon,
+                    comments: vec![],
+                    trailing: None,
+                });
+                continue;
+            }
+            let node = self.statement()?;
+            let semicolon = self.eat(";");
+            statements.push(Statement {
+                node,
+                semicolon,
+                comments: vec![],
+                trailing: None,
+            });
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Node, ParseError> {
+        let target = self.expression(1)?;
+        if self.eat("=") {
+            let value = self.statement()?;
+            let span = target.span.to(value.span);
+            return Ok(Node {
+                expr: Expr::Assign {
+                    target: Box::new(target),
+                    value: Box::new(value),
+                },
+                span,
+            });
+        }
+        Ok(target)
+    }
+
+    // Precedence climbing over the binary operators
+    fn expression(&mut self, min: usize) -> Result<Node, ParseError> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek() {
+            let precedence = match binary_precedence(op) {
+                Some(p) if p >= min => p,
+                _ => break,
+            };
+            self.pos += 1;
+            let next_min = if op.is_left_associative() {
+                precedence + 1
+            } else {
+                precedence
+            };
+            let right = self.expression(next_min)?;
+            let span = left.span.to(right.span);
+            left = Node {
+                expr: Expr::Binary {
+                    op: op.value.clone(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        let token = match self.peek() {
+            Some(t) => t,
+            None => return self.error("expected a value".to_string(), None),
+        };
+
+        // Signs bind looser than powers, -2 ^ 2 is -(2 ^ 2)
+        let negative_base = token.token_type == TokenTypes::Number
+            && token.value.starts_with('-')
+            && self
+                .tokens
+                .get(self.pos + 1)
+                .is_some_and(|t| t.token_type == TokenTypes::Symbol && t.value == "^");
+        if negative_base {
+            let mut positive = token.clone();
+            positive.value.remove(0);
+            let mut rest = self.tokens[self.pos + 1..].to_vec();
+            rest.insert(0, positive);
+            let mut cursor = Cursor {
+                tokens: &rest,
+                pos: 0,
+            };
+            let operand = cursor.expression(7)?;
+            self.pos += cursor.pos;
+            let span = Span::of(token).to(operand.span);
+            return Ok(Node {
+                expr: Expr::Unary {
+                    op: "-".to_string(),
+                    operand: Box::new(operand),
+                },
+                span,
+            });
+        }
+
+        if token.token_type == TokenTypes::Symbol && matches!(token.value.as_str(), "-" | "+" | "!")
+        {
+            self.pos += 1;
+            let operand = if token.value == "!" {
+                self.unary()?
+            } else {
+                self.expression(7)?
+            };
+            let span = Span::of(token).to(operand.span);
+            return Ok(Node {
+                expr: Expr::Unary {
+                    op: token.value.clone(),
+                    operand: Box::new(operand),
+                },
+                span,
+            });
+        }
+        self.postfix()
+    }
+
+    // A value followed by any .field accesses
+    fn postfix(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.primary()?;
+        while let Some(field) = self
+            .peek()
+            .filter(|t| t.token_type == TokenTypes::Identifier && t.value.starts_with('.'))
+        {
+            self.pos += 1;
+            let now = self.eat(":");
+            let span = node.span.to(Span::of(field));
+            node = Node {
+                expr: Expr::Field {
+                    target: Box::new(node),
+                    path: split_path(&field.value[1..]),
+                    now,
+                },
+                span,
+            };
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, ParseError> {
+        let token = match self.next() {
+            Some(t) => t,
+            None => return self.error("expected a value".to_string(), None),
+        };
+        let span = Span::of(token);
+        let expr = match token.token_type {
+            TokenTypes::Number => Expr::Number(token.value.clone()),
+            TokenTypes::Bool => Expr::Bool(token.value == "true"),
+            TokenTypes::Nothing => Expr::Nothing,
+            TokenTypes::String => Expr::String {
+                parts: string_parts(token)?,
+                token: token.clone(),
+            },
+            TokenTypes::List => {
+                let mut cursor = Cursor {
+                    tokens: &token.block,
+                    pos: 0,
+                };
+                let mut items = vec![];
+                while cursor.peek().is_some() {
+                    items.push(cursor.statement()?);
+                }
+                Expr::List {
+                    items,
+                    token: token.clone(),
+                }
+            }
+            TokenTypes::Block => block(token)?,
+            TokenTypes::Identifier => {
+                if token.value.starts_with('.') {
+                    return self.error(
+                        format!("{} has nothing to look in", token.value),
+                        Some(token),
+                    );
+                }
+                let path = split_path(&token.value);
+                if self.at("(") {
+                    return self.call(path, false, span);
+                }
+                Expr::Name {
+                    path,
+                    now: self.eat(":"),
+                }
+            }
+            TokenTypes::Function => {
+                let name = vec![token.value.clone()];
+                if self.at("(") {
+                    return self.call(name, true, span);
+                }
+                // Keywords without brackets take everything up to the ;
+                let mut args = vec![];
+                while self.peek().is_some() && !self.at(";") && !self.at(")") && !self.at(",") {
+                    args.push(self.statement()?);
+                }
+                let span = args.last().map_or(span, |a| span.to(a.span));
+                return Ok(Node {
+                    expr: Expr::Call {
+                        name,
+                        builtin: true,
+                        args,
+                        parens: false,
+                        commas: vec![],
+                    },
+                    span,
+                });
+            }
+            TokenTypes::Symbol => match token.value.as_str() {
+                "(" => {
+                    let inner = self.statement()?;
+                    if !self.eat(")") {
+                        return self.error("missing ')'".to_string(), Some(token));
+                    }
+                    Expr::Group(Box::new(inner))
+                }
+                "@" => {
+                    let inner = self.postfix()?;
+                    let span = span.to(inner.span);
+                    return Ok(Node {
+                        expr: Expr::Run(Box::new(inner)),
+                        span,
+                    });
+                }
+                ")" | "=" | ";" => {
+                    return self.error(format!("unexpected '{}'", token.value), Some(token))
+                }
+                _ => Expr::Symbol(token.value.clone()),
+            },
+            TokenTypes::Break
+            | TokenTypes::Range
+            | TokenTypes::Generator
+            | TokenTypes::Set
+            | TokenTypes::Deque
+            | TokenTypes::Queue
+            | TokenTypes::SortedMap => Expr::Value(token.clone()),
+        };
+        Ok(Node { expr, span })
+    }
+
+    // The arguments of a call, the cursor is on the opening bracket
+    fn call(&mut self, name: Vec<String>, builtin: bool, span: Span) -> Result<Node, ParseError> {
+        let open = self.next();
+        let mut args = vec![];
+        let mut commas = vec![];
+        loop {
+            if self.eat(",") {
+                commas.push(args.len());
+                continue;
+            }
+            if let Some(close) = self.peek().filter(|_| self.at(")")) {
+                self.pos += 1;
+                return Ok(Node {
+                    expr: Expr::Call {
+                        name,
+                        builtin,
+                        args,
+                        parens: true,
+                        commas,
+                    },
+                    span: span.to(Span::of(close)),
+                });
+            }
+            if self.peek().is_none() {
+                return self.error(format!("missing ')' for {}", name.join(".")), open);
+            }
+            args.push(self.statement()?);
+        }
+    }
+}
+
+fn split_path(value: &str) -> Vec<String> {
+    value.split('.').map(|s| s.to_string()).collect()
+}
+
+// Parameters are the names in front of the ~ at the start of a block
+fn block(token: &Token) -> Result<Expr, ParseError> {
+    let tokens = match token.block.first() {
+        Some(first) if first.token_type == TokenTypes::Break => &token.block[1..],
+        _ => &token.block[..],
+    };
+
+    let mut params = vec![];
+    let mut start = 0;
+    let mut pos = 0;
+    while let (Some(name), Some(colon)) = (tokens.get(pos), tokens.get(pos + 1)) {
+        if name.token_type != TokenTypes::Identifier || colon.value != ":" {
+            break;
+        }
+        params.push(name.value.clone());
+        pos += 2;
+    }
+    if tokens
+        .get(pos)
+        .is_some_and(|t| t.token_type == TokenTypes::Symbol && t.value == "~")
+    {
+        start = pos + 1;
+    } else {
+        params.clear();
+    }
+
+    let body = Cursor {
+        tokens: &tokens[start..],
+        pos: 0,
+    }
+    .sequence()?;
+    Ok(Expr::Block {
+        params,
+        body,
+        token: token.clone(),
+        end_comments: vec![],
+    })
+}
+
+fn string_parts(token: &Token) -> Result<Vec<StringPart>, ParseError> {
+    if token.block.is_empty() {
+        return Ok(vec![StringPart::Text(token.value.clone())]);
+    }
+    let mut parts = vec![];
+    for part in &token.block {
+        if part.token_type == TokenTypes::Block {
+            parts.push(StringPart::Interpolation(parse(&part.block)?));
+        } else if !part.value.is_empty() {
+            parts.push(StringPart::Text(part.value.clone()));
+        }
+    }
+    Ok(parts)
+}
+
+// Hands the comments the lexer kept aside to the statements they sit
+// next to, giving back the ones after the last statement
+pub fn attach_comments(statements: &mut [Statement], comments: &[Comment]) -> Vec<String> {
+    let mut pending: Vec<&Comment> = comments.iter().collect();
+    attach_body(statements, &mut pending, usize::MAX)
+}
+
+// Takes the comments on lines before end, the earliest is first
+fn take_comments(pending: &mut Vec<&Comment>, end: usize) -> Vec<String> {
+    let n = pending.iter().take_while(|c| c.line_number < end).count();
+    pending
+        .drain(..n)
+        .map(|c| c.text.trim_end().to_string())
+        .collect()
+}
+
+// Attaches the comments on lines before end to a sequence of statements.
+// A comment goes before the statement after it, or after the last
+// statement ending on its line. Comments inside a statement but in no
+// block go before it
+fn attach_body(body: &mut [Statement], pending: &mut Vec<&Comment>, end: usize) -> Vec<String> {
+    for n in 0..body.len() {
+        let span = body[n].node.span;
+        let mut comments = take_comments(pending, span.line_number);
+        attach_node(&mut body[n].node, pending, &mut comments);
+        comments.extend(take_comments(pending, span.end_line_number));
+
+        let last_on_line = body
+            .get(n + 1)
+            .is_none_or(|next| next.node.span.line_number > span.end_line_number);
+        if last_on_line && span.end_line_number < end {
+            body[n].trailing = take_comments(pending, span.end_line_number + 1).pop();
+        }
+        body[n].comments = comments;
+    }
+    take_comments(pending, end)
+}
+
+// Gives each block in a node the comments between its braces, the
+// comments before a block go into before
+fn attach_node(node: &mut Node, pending: &mut Vec<&Comment>, before: &mut Vec<String>) {
+    match &mut node.expr {
+        // The block token sits at the closing brace and starts with a
+        // break at the opening one
+        Expr::Block {
+            body,
+            token,
+            end_comments,
+            ..
+        } => {
+            let start = token.block.first().map_or(token.line_number, |t| t.line_number);
+            before.extend(take_comments(pending, start));
+            *end_comments = attach_body(body, pending, token.line_number);
+        }
+        Expr::Call { args: nodes, .. } | Expr::List { items: nodes, .. } => {
+            for node in nodes {
+                attach_node(node, pending, before)
+            }
+        }
+        Expr::Binary { left, right, .. } => {
+            attach_node(left, pending, before);
+            attach_node(right, pending, before)
+        }
+        Expr::Assign { target, value } => {
+            attach_node(target, pending, before);
+            attach_node(value, pending, before)
+        }
+        Expr::Field { target: inner, .. }
+        | Expr::Unary { operand: inner, .. }
+        | Expr::Group(inner)
+        | Expr::Run(inner) => attach_node(inner, pending, before),
+        _ => {}
+    }
+}
+
+// Renders a syntax tree back to source in one canonical layout
+pub fn pretty(statements: &[Statement]) -> String {
+    let mut out = String::new();
+    let mut spread = false;
+    for (n, statement) in statements.iter().enumerate() {
+        let text = render_statement(statement, 0);
+        // Multi line statements get a blank line either side
+        if n > 0 && (spread || text.contains('\n')) {
+            out.push('\n');
+        }
+        spread = text.contains('\n');
+        out.push_str(&commented(statement, &text, 0));
+        out.push('\n');
+    }
+    out
+}
+
+// Renders a whole file, keeping the comments the lexer found in it
+pub fn pretty_file(mut statements: Vec<Statement>, comments: &[Comment]) -> String {
+    let end = attach_comments(&mut statements, comments);
+    let mut out = pretty(&statements);
+    for comment in end {
+        out.push_str(&comment);
+        out.push('\n');
+    }
+    out
+}
+
+const INDENT: usize = 4;
+
+// Blocks longer than this are spread over several lines
+const INLINE_WIDTH: usize = 60;
+
+// A rendered statement with its comments, each line indented
+fn commented(statement: &Statement, text: &str, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    let mut out = String::new();
+    for comment in &statement.comments {
+        out.push_str(&format!("{}{}\n", pad, comment));
+    }
+    out.push_str(&pad);
+    out.push_str(text);
+    if let Some(comment) = &statement.trailing {
+        out.push(' ');
+        out.push_str(comment);
+    }
+    out
+}
+
+fn render_statement(statement: &Statement, indent: usize) -> String {
+    let mut line = render(&statement.node, indent);
+    if statement.semicolon {
+        line.push(';');
+    }
+    line
+}
+
+fn render(node: &Node, indent: usize) -> String {
+    match &node.expr {
+        Expr::Number(value) => value.clone(),
+        Expr::Bool(value) => value.to_string(),
+        Expr::Nothing => "_".to_string(),
+        Expr::String { parts, .. } => {
+            let mut out = String::from("\"");
+            for part in parts {
+                match part {
+                    StringPart::Text(text) => out.push_str(&escape(text)),
+                    StringPart::Interpolation(statements) => {
+                        let inner: Vec<String> = statements
+                            .iter()
+                            .map(|s| render_statement(s, indent))
+                            .collect();
+                        out.push('{');
+                        out.push_str(&inner.join(" "));
+                        out.push('}');
+                    }
+                }
+            }
+            out.push('"');
+            out
+        }
+        Expr::Name { path, now } => format!("{}{}", path.join("."), if *now { ":" } else { "" }),
+        Expr::Call {
+            name,
+            args,
+            parens,
+            commas,
+            ..
+        } => {
+            let args: Vec<String> = args.iter().map(|a| render(a, indent)).collect();
+            if *parens {
+                let mut inner = String::new();
+                for (n, arg) in args.iter().enumerate() {
+                    if commas.contains(&n) && n > 0 {
+                        inner.push_str(", ");
+                    } else if n > 0 {
+                        inner.push(' ');
+                    }
+                    inner.push_str(arg);
+                }
+                format!("{}({})", name.join("."), inner)
+            } else if args.is_empty() {
+                name.join(".")
+            } else {
+                format!("{} {}", name.join("."), args.join(" "))
+            }
+        }
+        Expr::Field { target, path, now } => format!(
+            "{}.{}{}",
+            render(target, indent),
+            path.join("."),
+            if *now { ":" } else { "" }
+        ),
+        Expr::Unary { op, operand } => {
+            let inner = render(operand, indent);
+            match operand.expr {
+                Expr::Assign { .. } => format!("{}({})", op, inner),
+                Expr::Binary { .. } if op == "!" => format!("{}({})", op, inner),
+                _ => format!("{}{}", op, inner),
+            }
+        }
+        Expr::Binary { op, left, right } => {
+            let precedence = binary_op_precedence(op);
+            let left_assoc = op != "^";
+            let side = |child: &Node, right_side: bool| {
+                let text = render(child, indent);
+                match &child.expr {
+                    Expr::Binary { op: inner, .. } => {
+                        let inner = binary_op_precedence(inner);
+                        if inner < precedence || (inner == precedence && right_side == left_assoc) {
+                            format!("({})", text)
+                        } else {
+                            text
+                        }
+                    }
+                    Expr::Assign { .. } => format!("({})", text),
+                    _ => text,
+                }
+            };
+            format!("{} {} {}", side(left, false), op, side(right, true))
+        }
+        Expr::Assign { target, value } => {
+            format!("{} = {}", render(target, indent), render(value, indent))
+        }
+        Expr::Block {
+            params,
+            body,
+            end_comments,
+            ..
+        } => render_block(params, body, end_comments, indent),
+        Expr::List { items, .. } => {
+            let items: Vec<String> = items.iter().map(|i| render(i, indent)).collect();
+            format!("[{}]", items.join(" "))
+        }
+        Expr::Group(inner) => format!("({})", render(inner, indent)),
+        Expr::Run(inner) => format!("@{}", render(inner, indent)),
+        Expr::Symbol(value) => value.clone(),
+        Expr::Value(token) => token.value.clone(),
+    }
+}
+
+fn binary_op_precedence(op: &str) -> usize {
+    Token {
+        token_type: TokenTypes::Symbol,
+        value: op.to_string(),
+        line_number: 0,
+        row: 0,
+        block: vec![],
+        proxy: None,
+    }
+    .precedence()
+}
+
+fn render_block(
+    params: &[String],
+    body: &[Statement],
+    end_comments: &[String],
+    indent: usize,
+) -> String {
+    let head: String = params.iter().map(|p| format!("{}: ", p)).collect();
+    let head = if params.is_empty() {
+        head
+    } else {
+        format!("{}~", head)
+    };
+
+    // Comments keep a block on several lines
+    let has_comments = !end_comments.is_empty()
+        || body
+            .iter()
+            .any(|s| !s.comments.is_empty() || s.trailing.is_some());
+    if body.len() <= 1 && !has_comments {
+        let inner: String = body.iter().map(|s| render_statement(s, indent)).collect();
+        let line = match (head.is_empty(), inner.is_empty()) {
+            (true, _) => format!("{{{}}}", inner),
+            (false, true) => format!("{{{}}}", head),
+            (false, false) => format!("{{{} {}}}", head, inner),
+        };
+        if !line.contains('\n') && line.chars().count() <= INLINE_WIDTH {
+            return line;
+        }
+    }
+
+    let inner = indent + INDENT;
+    let mut lines = vec!["{".to_string()];
+    if !head.is_empty() {
+        lines.push(format!("{}{}", " ".repeat(inner), head));
+    }
+    for statement in body {
+        let text = render_statement(statement, inner);
+        lines.push(commented(statement, &text, inner));
+    }
+    for comment in end_comments {
+        lines.push(format!("{}{}", " ".repeat(inner), comment));
+    }
+    lines.push(format!("{}}}", " ".repeat(indent)));
+    lines.join("\n")
+}
+
+// Writes text so the lexer reads it back unchanged
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '{' => out.push_str("\\{"),
+            '}' => out.push_str("\\}"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,21 +1,43 @@
+use std::{iter::Peekable, str::Chars};
+
+use unicode_xid::UnicodeXID;
+
 use crate::{
-    string_utils::is_string_number,
+    string_utils::{parse_number, show_error},
     token::{Token, TokenTypes},
 };
 
+// A problem found while lexing, programs with any of these never run
+pub struct LexError {
+    pub message: String,
+    pub line_number: usize,
+    pub row: usize,
+}
+
+// A # comment, kept out of the tokens so that only --fmt sees it
+pub struct Comment {
+    pub text: String,
+    pub line_number: usize,
+}
+
 pub struct Lexer {
     pub source: String,
+    file: String,
     buffer: String,
     line_number: usize,
     row: usize,
-    is_parsing_stringdq: bool,
-    is_parsing_stringsq: bool,
     is_parsing_comment: bool,
     pub block_stack: Vec<Vec<Token>>,
     function_keywords: Vec<String>,
     bool_keywords: Vec<String>,
-    is_skip: bool,
+    pub errors: Vec<LexError>,
+    pub comments: Vec<Comment>,
+    // Where each block or list that is still open was started
+    open_delimiters: Vec<(char, usize, usize)>,
 }
+// Symbols that are lexed as one token when written next to each other
+const TWO_CHAR_OPERATORS: [&str; 7] = ["<=", ">=", "==", "!=", "//", "&&", "||"];
+
 fn manticore_functions() -> Vec<String> {
     vec![
         // basic output
@@ -38,6 +60,19 @@ fn manticore_functions() -> Vec<String> {
         "sec".to_string(),
         // string function
         "concat".to_string(),
+        "len".to_string(),
+        "substr".to_string(),
+        "find".to_string(),
+        "replace".to_string(),
+        "split".to_string(),
+        "join".to_string(),
+        "upper".to_string(),
+        "lower".to_string(),
+        "trim".to_string(),
+        "starts_with".to_string(),
+        "ends_with".to_string(),
+        "repeat".to_string(),
+        "reverse".to_string(),
         // heap control
         "set".to_string(),
         "var".to_string(),
@@ -48,8 +83,16 @@ fn manticore_functions() -> Vec<String> {
         "neg".to_string(),
         "sqrt".to_string(),
         "pow".to_string(),
+        "floor".to_string(),
+        "ceil".to_string(),
+        "round".to_string(),
         // list functions
         "range".to_string(),
+        "range_step".to_string(),
+        "generate".to_string(),
+        "collect".to_string(),
+        "slice".to_string(),
+        "slice_step".to_string(),
         // loop functions
         "for".to_string(),
         "loop".to_string(),
@@ -60,6 +103,7 @@ fn manticore_functions() -> Vec<String> {
         // import
         "import".to_string(),
         "store_import".to_string(),
+        "export".to_string(),
         // os control
         "command".to_string(),
         // vm function
@@ -73,6 +117,12 @@ fn manticore_functions() -> Vec<String> {
         "lss".to_string(),
         // input
         "readln".to_string(),
+        // files
+        "read_file".to_string(),
+        "write_file".to_string(),
+        "file_exists".to_string(),
+        // testing
+        "assert".to_string(),
         // random function
         "randomf".to_string(),
         "random_int".to_string(),
@@ -84,6 +134,39 @@ fn manticore_functions() -> Vec<String> {
         "insert".to_string(),
         "remove".to_string(),
         "append".to_string(),
+        // higher order list functions
+        "map".to_string(),
+        "filter".to_string(),
+        "reduce".to_string(),
+        "fold".to_string(),
+        "any".to_string(),
+        "all".to_string(),
+        "zip".to_string(),
+        "enumerate".to_string(),
+        "flatten".to_string(),
+        "take".to_string(),
+        "drop".to_string(),
+        // sorting
+        "sort".to_string(),
+        "sort_by".to_string(),
+        "sort_with".to_string(),
+        // set functions
+        "set_of".to_string(),
+        "add".to_string(),
+        "contains".to_string(),
+        "union".to_string(),
+        "intersection".to_string(),
+        "difference".to_string(),
+        // queue and map functions
+        "deque".to_string(),
+        "push_front".to_string(),
+        "pop_front".to_string(),
+        "peek".to_string(),
+        "min_queue".to_string(),
+        "max_queue".to_string(),
+        "sorted_map".to_string(),
+        "put".to_string(),
+        "get".to_string(),
     ]
 }
 
@@ -93,16 +176,17 @@ impl Lexer {
         if let Ok(content) = std::fs::read_to_string(filename) {
             Lexer {
                 source: content,
+                file: filename.to_string(),
                 buffer: String::new(),
                 line_number: 1,
                 row: 0,
-                is_parsing_stringdq: false,
-                is_parsing_stringsq: false,
                 block_stack: vec![vec![]],
                 function_keywords: manticore_functions(),
                 bool_keywords: vec!["true".to_string(), "false".to_string()],
                 is_parsing_comment: false,
-                is_skip: false,
+                errors: vec![],
+                comments: vec![],
+                open_delimiters: vec![],
             }
         } else {
             println!(
@@ -117,16 +201,17 @@ impl Lexer {
     pub fn new_from_string(input: &str) -> Self {
         Lexer {
             source: input.to_string(),
+            file: String::new(),
             buffer: String::new(),
             line_number: 1,
             row: 0,
-            is_parsing_stringdq: false,
-            is_parsing_stringsq: false,
             block_stack: vec![vec![]],
             function_keywords: manticore_functions(),
             bool_keywords: vec!["true".to_string(), "false".to_string()],
             is_parsing_comment: false,
-            is_skip: false,
+            errors: vec![],
+            comments: vec![],
+            open_delimiters: vec![],
         }
     }
 
@@ -142,18 +227,33 @@ impl Lexer {
 
     // This function is used to check to see if the current
     // buffer is either a (number,function,bool,identifier)
-    fn check_token(&self) -> Option<Token> {
-        // Checking if buffer is numerical
+    fn check_token(&mut self) -> Option<Token> {
+        // Checking if buffer is numerical, anything that starts
+        // like a number has to be one
         if !self.buffer.is_empty() {
-            if is_string_number(&self.buffer) {
-                return Some(Token {
-                    token_type: TokenTypes::Number,
-                    value: self.buffer.clone(),
-                    line_number: self.line_number,
-                    row: self.row - self.buffer.len(),
-                    block: vec![],
-                    proxy: None,
-                });
+            if self
+                .buffer
+                .trim_start_matches('-')
+                .starts_with(|c: char| c.is_ascii_digit())
+            {
+                let row = self.row - self.buffer.chars().count();
+                if let Some(value) = parse_number(&self.buffer) {
+                    return Some(Token {
+                        token_type: TokenTypes::Number,
+                        value,
+                        line_number: self.line_number,
+                        row,
+                        block: vec![],
+                        proxy: None,
+                    });
+                }
+                self.error(
+                    format!("invalid number literal {}", self.buffer),
+                    self.line_number,
+                    row,
+                );
+                self.buffer.clear();
+                return None;
             } else {
                 // Checking if buffer is a function
                 if self.function_keywords.contains(&self.buffer) {
@@ -161,7 +261,7 @@ impl Lexer {
                         token_type: TokenTypes::Function,
                         value: self.buffer.clone(),
                         line_number: self.line_number,
-                        row: self.row - self.buffer.len(),
+                        row: self.row - self.buffer.chars().count(),
                         block: vec![],
                         proxy: None,
                     });
@@ -172,7 +272,7 @@ impl Lexer {
                         token_type: TokenTypes::Bool,
                         value: self.buffer.clone(),
                         line_number: self.line_number,
-                        row: self.row - self.buffer.len(),
+                        row: self.row - self.buffer.chars().count(),
                         block: vec![],
                         proxy: None,
                     });
@@ -184,7 +284,7 @@ impl Lexer {
                         token_type: TokenTypes::Nothing,
                         value: "_".to_string(),
                         line_number: self.line_number,
-                        row: self.row - self.buffer.len(),
+                        row: self.row - self.buffer.chars().count(),
                         block: vec![],
                         proxy: None,
                     });
@@ -193,7 +293,7 @@ impl Lexer {
                         token_type: TokenTypes::Identifier,
                         value: self.buffer.clone(),
                         line_number: self.line_number,
-                        row: self.row - self.buffer.len(),
+                        row: self.row - self.buffer.chars().count(),
                         block: vec![],
                         proxy: None,
                     });
@@ -203,76 +303,325 @@ impl Lexer {
         Option::None
     }
 
-    // Going through each char in the file or string
-    pub fn parse(&mut self) {
-        // Parsing strings double quote
-        for c in self.source.chars() {
-            if self.is_parsing_stringdq {
-                if c == '/' {
-                    self.is_skip = true;
-                    continue;
-                }
-                if c != '"' || self.is_skip {
-                    self.buffer.push(c);
-                    if self.is_skip {
-                        self.is_skip = false;
+    // Prints everything that went wrong while lexing, returns true
+    // if there was anything to print
+    pub fn report_errors(&self) -> bool {
+        for error in &self.errors {
+            show_error(&error.message, error.line_number, error.row, &self.file, "");
+        }
+        !self.errors.is_empty()
+    }
+
+    fn error(&mut self, message: String, line_number: usize, row: usize) {
+        self.errors.push(LexError {
+            message,
+            line_number,
+            row,
+        })
+    }
+
+    // Matches a closing brace or bracket with the innermost open one,
+    // returns false when there is nothing it could close
+    fn close(&mut self, closing: char) -> bool {
+        let expected = if closing == '}' { '{' } else { '[' };
+        match self.open_delimiters.pop() {
+            Some((open, _, _)) if open == expected => true,
+            Some((open, line_number, _)) => {
+                self.error(
+                    format!(
+                        "'{}' does not match the '{}' opened on line {}",
+                        closing, open, line_number
+                    ),
+                    self.line_number,
+                    self.row,
+                );
+                true
+            }
+            None => {
+                self.error(
+                    format!("unexpected '{}' with nothing to close", closing),
+                    self.line_number,
+                    self.row,
+                );
+                false
+            }
+        }
+    }
+
+    // Whether the last token finished a value, a minus after one
+    // subtracts. Nothing before it, (, {, operators and every item of a
+    // list start a value
+    fn follows_value(&self) -> bool {
+        if self.open_delimiters.last().is_some_and(|(open, _, _)| *open == '[') {
+            return false;
+        }
+        match self.block_stack.last().and_then(|tokens| tokens.last()) {
+            Some(t) => match t.token_type {
+                TokenTypes::Symbol => matches!(t.value.as_str(), ")" | ":"),
+                TokenTypes::Function | TokenTypes::Break => false,
+                _ => true,
+            },
+            None => false,
+        }
+    }
+
+    // Reads the next char, keeping line_number and row in step with it
+    fn next_char(&mut self, chars: &mut Peekable<Chars>) -> Option<char> {
+        let c = chars.next()?;
+        if c == '\n' {
+            self.line_number += 1;
+            self.row = 0;
+        } else {
+            self.row += 1;
+        }
+        Some(c)
+    }
+
+    // Decodes the escape sequence following a backslash inside a string
+    fn escape(&mut self, chars: &mut Peekable<Chars>) -> Option<char> {
+        let (line_number, start) = (self.line_number, self.row - 1);
+        let c = self.next_char(chars)?;
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            '{' => Some('{'),
+            '}' => Some('}'),
+            'u' => {
+                // \u{...} with one to six hex digits
+                let mut hex = String::new();
+                let mut closed = false;
+                if chars.peek() == Some(&'{') {
+                    self.next_char(chars);
+                    while chars.peek().is_some_and(|h| h.is_ascii_hexdigit()) {
+                        if let Some(h) = self.next_char(chars) {
+                            hex.push(h);
+                        }
                     }
-                    continue;
-                } else {
-                    self.is_parsing_stringdq = false;
-                    if let Some(vec_last) = self.block_stack.last_mut() {
-                        vec_last.push(Token {
-                            token_type: TokenTypes::String,
-                            value: self.buffer.clone(),
-                            line_number: self.line_number,
-                            row: self.row,
-                            block: vec![],
-                            proxy: None,
-                        })
+                    closed = chars.peek() == Some(&'}');
+                    if closed {
+                        self.next_char(chars);
                     }
-                    self.row += self.buffer.len() + 1;
-                    self.buffer.clear();
-                    continue;
                 }
+                let decoded = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| closed && hex.len() <= 6)
+                    .and_then(char::from_u32);
+                if decoded.is_none() {
+                    self.error(
+                        format!("invalid unicode escape \\u{{{}}}", hex),
+                        line_number,
+                        start,
+                    );
+                }
+                decoded
             }
+            _ => {
+                self.error(format!("unknown escape sequence \\{}", c), line_number, start);
+                None
+            }
+        }
+    }
 
-            // Parsing strings single quotes
-            if self.is_parsing_stringsq {
-                if c == '/' {
-                    self.is_skip = true;
-                    continue;
+    // Reads the expression of a {...} inside a string and lexes it,
+    // braces and quotes inside the expression are allowed to nest
+    fn interpolation(&mut self, chars: &mut Peekable<Chars>) -> Option<Token> {
+        let (line_number, start) = (self.line_number, self.row);
+        let mut expression = String::new();
+        let mut depth = 0;
+        let mut quote = None;
+        loop {
+            let c = match self.next_char(chars) {
+                Some(c) => c,
+                None => {
+                    self.error(
+                        "unterminated interpolation in string".to_string(),
+                        line_number,
+                        start - 1,
+                    );
+                    return None;
                 }
-                if c != '\'' || self.is_skip {
-                    self.buffer.push(c);
-                    if self.is_skip {
-                        self.is_skip = false;
+            };
+            match (quote, c) {
+                (Some(_), '\\') => {
+                    expression.push(c);
+                    if let Some(escaped) = self.next_char(chars) {
+                        expression.push(escaped)
                     }
                     continue;
-                } else {
-                    self.is_parsing_stringsq = false;
-                    if let Some(vec_last) = self.block_stack.last_mut() {
-                        vec_last.push(Token {
-                            token_type: TokenTypes::String,
-                            value: self.buffer.clone(),
-                            line_number: self.line_number,
-                            row: self.row,
-                            block: vec![],
-                            proxy: None,
-                        })
-                    }
-                    self.row += self.buffer.len() + 1;
+                }
+                (Some(q), _) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') if depth == 0 => break,
+                (None, '}') => depth -= 1,
+                _ => {}
+            }
+            expression.push(c);
+        }
+
+        let mut lexer = Lexer::new_from_string(&expression);
+        lexer.line_number = line_number;
+        lexer.row = start;
+        lexer.parse();
+        self.errors.append(&mut lexer.errors);
+        Some(Token {
+            token_type: TokenTypes::Block,
+            value: expression,
+            line_number,
+            row: start,
+            block: lexer.block_stack[0].clone(),
+            proxy: None,
+        })
+    }
+
+    // Reads string characters into the buffer until the closing quote,
+    // or until the input runs out when there is none. Returns whether
+    // the string was closed
+    fn decode(
+        &mut self,
+        chars: &mut Peekable<Chars>,
+        quote: Option<char>,
+        raw: bool,
+        parts: &mut Vec<Token>,
+    ) -> bool {
+        while let Some(c) = self.next_char(chars) {
+            if Some(c) == quote {
+                return true;
+            }
+            if raw {
+                self.buffer.push(c);
+                continue;
+            }
+            if c == '\\' {
+                if let Some(decoded) = self.escape(chars) {
+                    self.buffer.push(decoded);
+                }
+                continue;
+            }
+            // {expression} is filled in when the string is used, an
+            // empty {} is kept as it is
+            if c == '{' && chars.peek() != Some(&'}') {
+                if let Some(expression) = self.interpolation(chars) {
+                    parts.push(Token {
+                        token_type: TokenTypes::String,
+                        value: self.buffer.clone(),
+                        line_number: self.line_number,
+                        row: self.row,
+                        block: vec![],
+                        proxy: None,
+                    });
+                    parts.push(expression);
                     self.buffer.clear();
-                    continue;
                 }
+                continue;
+            }
+            self.buffer.push(c);
+        }
+        quote.is_none()
+    }
+
+    // Reads a string literal starting at its opening quote. Raw strings
+    // keep every character as written, tripled quotes make a multi-line
+    // string with the indentation its lines share removed
+    fn string(&mut self, chars: &mut Peekable<Chars>, quote: char, raw: bool) {
+        let opened = (self.line_number, self.row);
+        let mut ahead = chars.clone();
+        let triple = ahead.next() == Some(quote) && ahead.next() == Some(quote);
+        self.row += 1;
+        if triple {
+            self.next_char(chars);
+            self.next_char(chars);
+        }
+        let (line_number, row) = (self.line_number, self.row);
+
+        let mut parts = vec![];
+        let closed = if triple {
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some(c) = self.next_char(chars) {
+                if c == quote && chars.clone().take(2).filter(|n| *n == quote).count() == 2 {
+                    self.next_char(chars);
+                    self.next_char(chars);
+                    closed = true;
+                    break;
+                }
+                text.push(c);
+            }
+
+            // Escapes are decoded after the indentation is gone, positions
+            // inside the string are only kept accurate to the line
+            let end = (self.line_number, self.row);
+            if text.starts_with('\n') {
+                self.line_number = line_number + 1;
             }
+            self.decode(&mut dedent(&text).chars().peekable(), None, raw, &mut parts);
+            (self.line_number, self.row) = end;
+            closed
+        } else {
+            self.decode(chars, Some(quote), raw, &mut parts)
+        };
+        if !closed {
+            self.error("unterminated string".to_string(), opened.0, opened.1);
+        }
+
+        // Interpolated strings keep their pieces in the block
+        let mut value = self.buffer.clone();
+        if !parts.is_empty() {
+            parts.push(Token {
+                token_type: TokenTypes::String,
+                value: self.buffer.clone(),
+                line_number: self.line_number,
+                row: self.row,
+                block: vec![],
+                proxy: None,
+            });
+            value = parts
+                .iter()
+                .map(|part| match part.token_type {
+                    TokenTypes::Block => format!("{{{}}}", part.value),
+                    _ => part.value.clone(),
+                })
+                .collect();
+        }
+        if let Some(vec_last) = self.block_stack.last_mut() {
+            vec_last.push(Token {
+                token_type: TokenTypes::String,
+                value,
+                line_number,
+                row,
+                block: parts,
+                proxy: None,
+            })
+        }
+        self.buffer.clear();
+    }
+
+    // Going through each char in the file or string
+    pub fn parse(&mut self) {
+        let source = self.source.clone();
+        let mut chars = source.chars().peekable();
+        let mut previous: Option<char> = None;
+        while let Some(c) = chars.next() {
+            // Whether c starts the source or comes after whitespace
+            let spaced = previous.is_none_or(|p| p.is_whitespace());
+            previous = Some(c);
 
             // Parsing comments
             if self.is_parsing_comment {
                 if c != '\n' {
+                    if let Some(comment) = self.comments.last_mut() {
+                        comment.text.push(c)
+                    }
                     continue;
                 } else {
+                    // The newline still has to be counted below
                     self.is_parsing_comment = false;
-                    continue;
                 }
             }
 
@@ -301,16 +650,23 @@ impl Lexer {
                         }
                         self.buffer.clear();
                     }
+                    self.comments.push(Comment {
+                        text: c.to_string(),
+                        line_number: self.line_number,
+                    });
                     self.is_parsing_comment = true;
                 }
 
-                // Letters and numbers
-                'a'..='z' | 'A'..='Z' | '_' | '0'..='9' => {
+                // Letters and numbers, identifiers follow the unicode XID rules
+                c if c == '_' || c.is_ascii_digit() || c.is_xid_start() => {
+                    self.buffer.push(c);
+                }
+                c if !self.buffer.is_empty() && c.is_xid_continue() => {
                     self.buffer.push(c);
                 }
 
                 // Spaces
-                ' ' => {
+                c if c.is_whitespace() => {
                     if let Some(t) = self.check_token() {
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(t)
@@ -322,48 +678,100 @@ impl Lexer {
                     self.buffer.push(c);
                 }
 
-                // Symbols
-                '+' | '-' | '*' | '/' | '(' | ')' | '<' | '>' | '`' | '~' | '@' | '$' | '%'
-                | '^' | '&' | ',' | '?' | ';' | ':' | '=' => {
+                // A minus directly in front of a digit starts a negative
+                // number when a space or no value comes before it, so
+                // f(5 -1) takes -1 while x-1 and x - 1 subtract
+                '-' if self.buffer.is_empty()
+                    && chars.peek().is_some_and(|n| n.is_ascii_digit())
+                    && (spaced || !self.follows_value()) =>
+                {
+                    self.buffer.push(c);
+                }
+
+                // Negative indexes as in list.-1
+                '-' if self.buffer.ends_with('.')
+                    && chars.peek().is_some_and(|n| n.is_ascii_digit()) =>
+                {
+                    self.buffer.push(c);
+                }
+
+                // The sign of an exponent as in 1.5e-3
+                '-' | '+'
+                    if chars.peek().is_some_and(|n| n.is_ascii_digit())
+                        && ends_in_exponent(&self.buffer) =>
+                {
+                    self.buffer.push(c);
+                }
+
+                // Operators written with two characters
+                c if chars
+                    .peek()
+                    .is_some_and(|n| TWO_CHAR_OPERATORS.contains(&format!("{}{}", c, n).as_str())) =>
+                {
                     if let Some(t) = self.check_token() {
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(t)
                         }
                         self.buffer.clear();
                     }
+                    let mut operator = c.to_string();
+                    if let Some(n) = chars.next() {
+                        operator.push(n);
+                    }
 
                     if let Some(vec_last) = self.block_stack.last_mut() {
                         vec_last.push(Token {
                             token_type: TokenTypes::Symbol,
-                            value: c.to_string(),
+                            value: operator,
                             line_number: self.line_number,
                             row: self.row,
                             block: vec![],
                             proxy: None,
                         })
                     }
+                    self.row += 1;
                 }
 
-                // Double quotes (start parsing a string)
-                '"' => {
-                    if let Some(t) = self.check_token() {
+                // Symbols
+                '+' | '-' | '*' | '/' | '(' | ')' | '<' | '>' | '`' | '~' | '@' | '$' | '%'
+                | '^' | '&' | ',' | '?' | ';' | ':' | '=' | '!' => {
+                    if let Some(mut t) = self.check_token() {
+                        // A builtin's name written as len: is a variable
+                        if c == ':' && t.token_type == TokenTypes::Function {
+                            t.token_type = TokenTypes::Identifier
+                        }
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(t)
                         }
                         self.buffer.clear();
                     }
-                    self.is_parsing_stringdq = true;
+
+                    if let Some(vec_last) = self.block_stack.last_mut() {
+                        vec_last.push(Token {
+                            token_type: TokenTypes::Symbol,
+                            value: c.to_string(),
+                            line_number: self.line_number,
+                            row: self.row,
+                            block: vec![],
+                            proxy: None,
+                        })
+                    }
                 }
 
-                // Single quotes (starts parsing a string)
-                '\'' => {
+                // Strings, r"..." is a raw string
+                '"' | '\'' => {
+                    let raw = self.buffer == "r";
+                    if raw {
+                        self.buffer.clear();
+                    }
                     if let Some(t) = self.check_token() {
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(t)
                         }
                         self.buffer.clear();
                     }
-                    self.is_parsing_stringsq = true;
+                    self.string(&mut chars, c, raw);
+                    continue;
                 }
 
                 // Parsing blocks
@@ -376,6 +784,7 @@ impl Lexer {
                     }
 
                     self.block_stack.push(vec![]);
+                    self.open_delimiters.push((c, self.line_number, self.row));
 
                     if let Some(vec_last) = self.block_stack.last_mut() {
                         vec_last.push(Token {
@@ -397,6 +806,10 @@ impl Lexer {
                         self.buffer.clear();
                     };
 
+                    if !self.close(c) {
+                        self.row += 1;
+                        continue;
+                    }
                     if let Some(list) = self.block_stack.pop() {
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(Token {
@@ -421,6 +834,7 @@ impl Lexer {
                     }
 
                     self.block_stack.push(vec![]);
+                    self.open_delimiters.push((c, self.line_number, self.row));
                 }
 
                 ']' => {
@@ -431,6 +845,10 @@ impl Lexer {
                         self.buffer.clear();
                     };
 
+                    if !self.close(c) {
+                        self.row += 1;
+                        continue;
+                    }
                     if let Some(list) = self.block_stack.pop() {
                         if let Some(vec_last) = self.block_stack.last_mut() {
                             vec_last.push(Token {
@@ -445,7 +863,11 @@ impl Lexer {
                     }
                 }
 
-                _ => println!("ERROR: {} is not defined. Line {1}", c, self.line_number),
+                _ => self.error(
+                    format!("unexpected character {:?}", c),
+                    self.line_number,
+                    self.row,
+                ),
             }
             self.row += 1;
         }
@@ -457,5 +879,43 @@ impl Lexer {
             }
             self.buffer.clear();
         };
+
+        for (open, line_number, row) in std::mem::take(&mut self.open_delimiters) {
+            self.error(format!("unclosed '{}'", open), line_number, row);
+        }
     }
 }
+
+// Drops the line break after the opening quotes, the line holding the
+// closing quotes if it is only whitespace, and the indentation that all
+// the other lines share
+fn dedent(text: &str) -> String {
+    let text = text.strip_prefix('\n').unwrap_or(text);
+    let text = match text.rfind('\n') {
+        Some(i) if text[i + 1..].trim().is_empty() => &text[..i],
+        _ => text,
+    };
+    let indentation = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+    let common = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(indentation)
+        .min()
+        .unwrap_or(0);
+    text.split('\n')
+        .map(|line| {
+            let skip = common.min(indentation(line));
+            line.chars().skip(skip).collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Whether the buffer is a decimal number waiting for its exponent
+fn ends_in_exponent(buffer: &str) -> bool {
+    let digits = buffer.trim_start_matches('-');
+    digits.starts_with(|c: char| c.is_ascii_digit())
+        && !digits.starts_with("0x")
+        && !digits.starts_with("0X")
+        && digits.ends_with(['e', 'E'])
+}
@@ -3,8 +3,10 @@ extern crate colored;
 
 
 mod unit_test;
+mod ast;
 mod lexer;
 mod manticorevm;
+mod module_loader;
 mod parser;
 mod string_utils;
 mod token;
@@ -12,6 +14,7 @@ mod token;
 use clap::*;
 use manticorevm::ManitcoreVm;
 use parser::Parser;
+use string_utils::show_error;
 
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
@@ -36,14 +39,24 @@ fn main() {
                 .short('d')
                 .help("displays debug information"),
         )
+        .arg(
+            Arg::with_name("NO_PRELUDE")
+                .long("no-prelude")
+                .takes_value(false)
+                .help("skips loading the standard library"),
+        )
+        .arg(
+            Arg::with_name("FMT")
+                .long("fmt")
+                .takes_value(false)
+                .help("prints the file in canonical form instead of running it"),
+        )
         .get_matches();
 
     //used for bundling code and interpreter to create single file
-    // let std = include_str!("../std.core");
     // let program = include_str!("../test7.core");
     // if !program.is_empty() {
-    //     let mut lexer = lexer::Lexer::new_from_string(std);
-    //     lexer.add_input(program);
+    //     let mut lexer = lexer::Lexer::new_from_string(program);
     //     // Parse the file into tokens
     //     lexer.parse();
     //     let mut parser = Parser::new();
@@ -57,6 +70,7 @@ fn main() {
     //     if matches.is_present("DEBUG") {
     //         vm.debug = true;
     //     }
+    //     vm.load_prelude();
 
     //     // Execute the vm using parsed token list
     //     vm.execute();
@@ -71,17 +85,33 @@ fn main() {
 
         // Parse the file into tokens
         lexer.parse();
+        if lexer.report_errors() {
+            std::process::exit(1)
+        }
+
+        // Build the syntax tree, --fmt prints it back out
+        let tree = ast::parse_file(&lexer.block_stack[0], filename);
+        if matches.is_present("FMT") {
+            print!("{}", ast::pretty_file(tree, &lexer.comments));
+            std::process::exit(0)
+        }
+
         let mut parser = Parser::new();
         if matches.is_present("DEBUG") {
             parser.debug = true;
         }
 
-        // Store now parsed tokens into a new list
-        let shunted = parser.shunt(&lexer.block_stack[0]).clone();
+        // Store the lowered tree into a new list
+        let shunted = parser.lower(&tree).clone();
         let mut vm = ManitcoreVm::new(&shunted, filename);
         if matches.is_present("DEBUG") {
             vm.debug = true;
         }
+        vm.modules.borrow_mut().set_main(filename);
+        vm.modules.borrow_mut().set_search_path(search_path());
+        if !matches.is_present("NO_PRELUDE") {
+            vm.load_prelude();
+        }
 
         // Execute the vm using parsed token list
         vm.execute();
@@ -96,6 +126,10 @@ fn main() {
         let mut repl = String::new();
         let mut repl_debug: bool = false;
         let mut vm = ManitcoreVm::new(&[], "");
+        vm.modules.borrow_mut().set_search_path(search_path());
+        if !matches.is_present("NO_PRELUDE") {
+            vm.load_prelude();
+        }
         let mut parser = Parser::new();
         loop {
             // Repl prompt
@@ -110,6 +144,9 @@ fn main() {
 
                     let mut lexer = lexer::Lexer::new_from_string(&line);
                     lexer.parse();
+                    if lexer.report_errors() {
+                        continue;
+                    }
 
                     // Basic repl commands to check
                     if line.to_lowercase() == "exit" {
@@ -127,9 +164,17 @@ fn main() {
                         continue;
                     };
 
-                    // Shunt tokens and insert them into vm
+                    // Lower the line and insert it into vm, a line that
+                    // does not parse is reported and skipped
+                    let tree = match ast::parse(&lexer.block_stack[0]) {
+                        Ok(tree) => tree,
+                        Err(e) => {
+                            show_error(&e.message, e.line_number, e.row, "", "");
+                            continue;
+                        }
+                    };
                     parser._clear();
-                    let shunted = parser.shunt(&lexer.block_stack[0]).clone();
+                    let shunted = parser.lower(&tree).clone();
 
                     // Enable vm debug
                     if repl_debug {
@@ -162,3 +207,10 @@ fn main() {
         rl.save_history("history.txt").unwrap();
     }
 }
+
+// Directories listed in MANTICORE_PATH, searched for modules
+fn search_path() -> Vec<std::path::PathBuf> {
+    std::env::var_os("MANTICORE_PATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
@@ -1,9 +1,16 @@
 use rand::Rng;
 use std::process::Command;
-use std::{collections::HashMap, io::Write};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::Write,
+    rc::Rc,
+};
 
 use crate::{
+    ast,
     lexer::{self},
+    module_loader::{Module, ModuleLoader, SharedModules},
     parser::Parser,
     string_utils::{is_string_number, print_error, trim_newline},
     token::{Token, TokenTypes},
@@ -20,6 +27,33 @@ pub struct ManitcoreVm {
     pub method_call: bool,
     pub core_self: Vec<Token>,
     heap: HashMap<String, Token>,
+    pub modules: SharedModules,
+    // Names listed with export, None if the module never called it
+    exports: Option<Vec<String>>,
+    // Names hidden by imported modules and the module hiding them
+    private: HashMap<String, String>,
+}
+
+// Hands out the items of anything for and the list builtins walk over.
+// Ranges, generators and objects with next make them one at a time
+enum Items {
+    Ready(std::vec::IntoIter<Token>),
+    // [key value] pairs of an object read as a map
+    Map(std::vec::IntoIter<Token>),
+    Range {
+        range: Token,
+        n: usize,
+        count: usize,
+    },
+    Generator {
+        current: Token,
+        step: Token,
+        started: bool,
+    },
+    Object {
+        method: Token,
+        state: HashMap<String, Token>,
+    },
 }
 
 impl ManitcoreVm {
@@ -35,6 +69,9 @@ impl ManitcoreVm {
             exit_loop: false,
             method_call: false,
             core_self: vec![],
+            modules: Rc::new(RefCell::new(ModuleLoader::new())),
+            exports: None,
+            private: HashMap::new(),
         }
     }
 
@@ -49,17 +86,7 @@ impl ManitcoreVm {
                 break;
             };
 
-            self.heap = self
-                .heap
-                .iter()
-                .filter_map(|(id, tok)| {
-                    if tok.value != "_" {
-                        Some((id.clone(), tok.clone()))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+            self.heap.retain(|_, tok| tok.value != "_");
 
             self.heap.remove("_");
             // for t in core_self {
@@ -82,7 +109,696 @@ impl ManitcoreVm {
         }
     }
 
+    // Runs the standard library bundled with the interpreter and makes
+    // what it defines visible from every scope
+    pub fn load_prelude(&mut self) {
+        let mut lexer = lexer::Lexer::new_from_string(include_str!("../std.core"));
+        lexer.parse();
+        if lexer.report_errors() {
+            std::process::exit(1)
+        }
+        let mut parser = Parser::new();
+        let shunted = parser.shunt(&lexer.block_stack[0]).clone();
+        let mut vm = ManitcoreVm::new(&shunted, "std.core");
+        vm.modules = self.modules.clone();
+        vm.execute();
+
+        let module = Module::new(vm.heap, &vm.exports);
+        self.modules.borrow_mut().set_prelude(module.public);
+    }
+
+    // Using a name an imported module kept to itself is an error
+    fn check_private(&self, tok: &Token) {
+        if tok.token_type != TokenTypes::Identifier {
+            return;
+        }
+        if let Some(module) = self.private.get(&tok.value) {
+            print_error(
+                format!("{} is not exported by module {}", tok.value, module).as_str(),
+                tok.line_number,
+                tok.row,
+                &self.file,
+                &self.last_instruction,
+            )
+        }
+    }
+
+    // Evaluates each expression of an interpolated string in the
+    // current scope and pushes the finished string
+    fn interpolate(&mut self, i: &Token) {
+        let mut text = String::new();
+        for part in &i.block {
+            if part.token_type != TokenTypes::Block {
+                text.push_str(&part.value);
+                continue;
+            }
+            let mut parser = Parser::new();
+            if self.debug {
+                parser.debug = true
+            }
+            let depth = self.execution_stack.len();
+            let shunted = parser.shunt(&part.block).clone();
+            for t in &shunted {
+                self.execute_token(t)
+            }
+            if self.execution_stack.len() > depth {
+                if let Some(value) = self.execution_stack.pop() {
+                    text.push_str(&value.display())
+                }
+                self.execution_stack.truncate(depth);
+            } else {
+                print_error(
+                    format!("{{{}}} did not produce a value", part.value).as_str(),
+                    part.line_number,
+                    part.row,
+                    &self.file,
+                    &self.last_instruction,
+                )
+            }
+        }
+        self.execution_stack.push(Token {
+            token_type: TokenTypes::String,
+            value: text,
+            line_number: i.line_number,
+            row: i.row,
+            block: vec![],
+            proxy: None,
+        });
+        self.last_instruction = i.value.to_owned();
+    }
+
+    // Runs an object's block on its own vm so its fields can be read
+    fn run_object(&self, block: &Token) -> ManitcoreVm {
+        let mut parser = Parser::new();
+        if self.debug {
+            parser.debug = true
+        }
+        let shunted = parser.shunt(&block.block).clone();
+        let mut vm = ManitcoreVm::new(&shunted, &block.value);
+        if self.debug {
+            vm.debug = true
+        }
+        vm.modules = self.modules.clone();
+        vm.execute();
+        vm
+    }
+
+    // Runs a callback the same way @ does, with args on its own stack,
+    // and returns what it leaves behind
+    fn call_block(&mut self, block: &Token, args: Vec<Token>, i: &Token) -> Token {
+        self.check_private(block);
+        let mut parser = Parser::new();
+        if self.debug {
+            parser.debug = true
+        }
+        let shunted = parser.shunt(&block.block).clone();
+        let mut vm = ManitcoreVm::new(&shunted, &block.value);
+        if self.debug {
+            vm.debug = true
+        }
+        vm.modules = self.modules.clone();
+        vm.execution_stack = args;
+        vm.execute();
+
+        match vm.execution_stack.pop() {
+            Some(result) if result.token_type != TokenTypes::Break => result,
+            _ => {
+                print_error(
+                    format!("the block given to {} did not return a value", i.value).as_str(),
+                    i.line_number,
+                    i.row,
+                    &self.file,
+                    &self.last_instruction,
+                );
+                Token {
+                    proxy: None,
+                    token_type: TokenTypes::Nothing,
+                    value: "_".to_string(),
+                    block: vec![],
+                    line_number: 0,
+                    row: 0,
+                }
+            }
+        }
+    }
+
+    // Callbacks for filter, any and all have to answer true or false
+    fn expect_bool(&self, result: &Token, i: &Token) -> bool {
+        if result.token_type != TokenTypes::Bool {
+            print_error(
+                format!("the block given to {} returned {}, expected a bool", i.value, result.value)
+                    .as_str(),
+                i.line_number,
+                i.row,
+                &self.file,
+                &self.last_instruction,
+            )
+        }
+        result.value == "true"
+    }
+
+    // for x: ... binds each item, for [i x] ... binds the position or
+    // key as well
+    fn loop_names(&self, ident: &Token, i: &Token) -> Vec<String> {
+        if ident.token_type == TokenTypes::List {
+            if ident.block.len() != 2
+                || ident.block.iter().any(|t| t.token_type != TokenTypes::Identifier)
+            {
+                print_error(
+                    "for expects one name or two names in brackets like [i x]",
+                    i.line_number,
+                    i.row,
+                    &self.file,
+                    &self.last_instruction,
+                )
+            }
+            ident.block.iter().map(|t| t.value.clone()).collect()
+        } else if let Some(p) = &ident.proxy {
+            vec![p.clone()]
+        } else {
+            vec![ident.value.clone()]
+        }
+    }
+
+    // Runs one pass of a for loop, returning false once the loop is broken
+    fn loop_body(
+        &mut self,
+        block: &Token,
+        names: &[String],
+        key: Token,
+        item: Token,
+        pairs: bool,
+    ) -> bool {
+        let mut vm = self.loop_vm(block, names, key, item, pairs);
+        vm.execute();
+        if vm.exit_loop {
+            return false;
+        }
+        self.heap = vm.heap.clone();
+        true
+    }
+
+    // A vm for one pass over a loop block with the loop names bound
+    fn loop_vm(
+        &self,
+        block: &Token,
+        names: &[String],
+        key: Token,
+        item: Token,
+        pairs: bool,
+    ) -> ManitcoreVm {
+        let mut parser = Parser::new();
+        if self.debug {
+            parser.debug = true
+        }
+        let mut shunted = parser.shunt(&block.block).clone();
+        shunted.remove(0);
+        let mut vm = ManitcoreVm::new(&shunted, &block.value);
+        if self.debug {
+            vm.debug = true
+        }
+        vm.modules = self.modules.clone();
+        vm.heap = self.heap.clone();
+
+        // Two names get the key and value of map pairs, anything else
+        // gives its position and the item
+        let values = if names.len() == 1 {
+            vec![item]
+        } else if pairs {
+            item.block
+        } else {
+            vec![key, item]
+        };
+        for (name, mut value) in names.iter().zip(values) {
+            value.proxy = Some(name.clone());
+            vm.heap.insert(name.clone(), value);
+        }
+        vm
+    }
+
+    // Starts walking over a value. Objects with a next method are asked
+    // for items, other objects are read as maps of their fields
+    fn items(&mut self, collection: &Token, i: &Token) -> Items {
+        match collection.token_type {
+            TokenTypes::List | TokenTypes::Set => Items::Ready(collection.block.clone().into_iter()),
+            TokenTypes::Deque => Items::Ready(collection.deque_items().to_vec().into_iter()),
+            TokenTypes::Queue => Items::Ready(collection.queue_items().into_iter()),
+            TokenTypes::SortedMap => Items::Map(collection.block.clone().into_iter()),
+            TokenTypes::String => {
+                let chars: Vec<Token> = collection
+                    .value
+                    .chars()
+                    .map(|c| Token {
+                        proxy: None,
+                        token_type: TokenTypes::String,
+                        value: c.to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
+                    .collect();
+                Items::Ready(chars.into_iter())
+            }
+            TokenTypes::Range => Items::Range {
+                range: collection.clone(),
+                n: 0,
+                count: range_len(collection),
+            },
+            TokenTypes::Generator => Items::Generator {
+                current: collection.block[0].clone(),
+                step: collection.block[1].clone(),
+                started: false,
+            },
+            TokenTypes::Block => {
+                let vm = self.run_object(collection);
+                if let Some(method) = vm
+                    .heap
+                    .get("next")
+                    .filter(|m| m.token_type == TokenTypes::Block)
+                {
+                    return Items::Object {
+                        method: method.clone(),
+                        state: vm.heap.clone(),
+                    };
+                }
+
+                // The name an object is stored under is not one of its fields
+                let mut fields: Vec<(String, Token)> = vm
+                    .heap
+                    .into_iter()
+                    .filter(|(k, _)| Some(k) != collection.proxy.as_ref())
+                    .collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                let pairs: Vec<Token> = fields
+                    .into_iter()
+                    .map(|(k, item)| {
+                        let key = Token {
+                            proxy: None,
+                            token_type: TokenTypes::String,
+                            value: k,
+                            block: vec![],
+                            line_number: 0,
+                            row: 0,
+                        };
+                        Token {
+                            proxy: None,
+                            token_type: TokenTypes::List,
+                            value: "list".to_string(),
+                            block: vec![key, item],
+                            line_number: 0,
+                            row: 0,
+                        }
+                    })
+                    .collect();
+                Items::Map(pairs.into_iter())
+            }
+            _ => {
+                print_error(
+                    format!("{} cannot walk over {}", i.value, collection.value).as_str(),
+                    i.line_number,
+                    i.row,
+                    &self.file,
+                    &self.last_instruction,
+                );
+                Items::Ready(vec![].into_iter())
+            }
+        }
+    }
+
+    // The next item, None once they have run out
+    fn next_item(&mut self, items: &mut Items, i: &Token) -> Option<Token> {
+        match items {
+            Items::Ready(list) | Items::Map(list) => list.next(),
+            Items::Range { range, n, count } => {
+                if n >= count {
+                    return None;
+                }
+                *n += 1;
+                Some(range_value(range, *n - 1))
+            }
+            // Generators start from the seed and run the block on the
+            // last value for each one after, _ ends them
+            Items::Generator {
+                current,
+                step,
+                started,
+            } => {
+                if current.token_type == TokenTypes::Nothing {
+                    return None;
+                }
+                if *started {
+                    *current = self.call_block(step, vec![current.clone()], i);
+                    if current.token_type == TokenTypes::Nothing {
+                        return None;
+                    }
+                }
+                *started = true;
+                Some(current.clone())
+            }
+            // next runs with the object's fields in scope so it can move
+            // them on between calls
+            Items::Object { method, state } => {
+                let mut parser = Parser::new();
+                if self.debug {
+                    parser.debug = true
+                }
+                let shunted = parser.shunt(&method.block).clone();
+                let mut vm = ManitcoreVm::new(&shunted, &method.value);
+                if self.debug {
+                    vm.debug = true
+                }
+                vm.modules = self.modules.clone();
+                vm.heap = std::mem::take(state);
+                vm.execute();
+                *state = vm.heap;
+
+                match vm.execution_stack.pop() {
+                    Some(item) if item.token_type == TokenTypes::Nothing => None,
+                    Some(item) if item.token_type != TokenTypes::Break => Some(item),
+                    _ => {
+                        print_error(
+                            "next did not return a value, return _ to stop",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn length_of(&self, collection: &Token, i: &Token) -> usize {
+        match collection.token_type {
+            TokenTypes::String => collection.value.chars().count(),
+            TokenTypes::Range => range_len(collection),
+            TokenTypes::Deque => collection.deque_items().len(),
+            TokenTypes::Queue => collection.block.len() - 1,
+            TokenTypes::List | TokenTypes::Set | TokenTypes::SortedMap | TokenTypes::Block => {
+                collection.block.len()
+            }
+            TokenTypes::Generator => {
+                print_error(
+                    "a generator has no length, take some of it first",
+                    i.line_number,
+                    i.row,
+                    &self.file,
+                    &self.last_instruction,
+                );
+                0
+            }
+            _ => {
+                print_error(
+                    format!("cannot take the length of {}", collection.display()).as_str(),
+                    i.line_number,
+                    i.row,
+                    &self.file,
+                    &self.last_instruction,
+                );
+                0
+            }
+        }
+    }
+
+    // Stops with an error unless the token is a string
+    fn expect_string(&self, text: &Token, i: &Token) {
+        if text.token_type != TokenTypes::String {
+            print_error(
+                format!("{} expected a string, got {}", i.value, text.display()).as_str(),
+                i.line_number,
+                i.row,
+                &self.file,
+                &self.last_instruction,
+            )
+        }
+    }
+
+    // Adds an item the way the collection keeps them. Lists and deques
+    // take it at the front or the back, queues and sets where it belongs
+    // and maps take [key value] pairs
+    fn put_item(&mut self, collection: &mut Token, item: Token, front: bool, i: &Token) {
+        match collection.token_type {
+            TokenTypes::Deque if front => deque_push_front(collection, item),
+            TokenTypes::Queue => self.queue_push(collection, item, i),
+            TokenTypes::Set => {
+                if let Err(n) = collection.block.binary_search_by(|t| t.set_cmp(&item)) {
+                    collection.block.insert(n, item)
+                }
+            }
+            TokenTypes::SortedMap => {
+                if item.token_type == TokenTypes::List && item.block.len() == 2 {
+                    let mut pair = item.block.into_iter();
+                    if let (Some(key), Some(value)) = (pair.next(), pair.next()) {
+                        map_put(collection, key, value)
+                    }
+                } else {
+                    print_error(
+                        format!("{} expected a [key value] pair for a sorted map, got {}", i.value, item.display())
+                            .as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            _ if front => collection.block.insert(0, item),
+            _ => collection.block.push(item),
+        }
+    }
+
+    // Takes the item pop or pop_front asks for, a queue always gives its
+    // top item
+    fn take_item(&mut self, collection: &mut Token, front: bool) -> Option<Token> {
+        match collection.token_type {
+            TokenTypes::Queue => queue_pop(collection),
+            TokenTypes::Deque if front => deque_pop_front(collection),
+            TokenTypes::Deque if collection.deque_items().is_empty() => None,
+            _ if front && !collection.block.is_empty() => Some(collection.block.remove(0)),
+            _ if front => None,
+            _ => collection.block.pop(),
+        }
+    }
+
+    // Puts an item in a queue. Its priority comes from the key block once
+    // and is kept next to it with a count so ties leave in the order they
+    // came
+    fn queue_push(&mut self, queue: &mut Token, item: Token, i: &Token) {
+        let key = queue.block[0].block[0].clone();
+        let priority = if key.token_type == TokenTypes::Block {
+            self.call_block(&key, vec![item.clone()], i)
+        } else {
+            item.clone()
+        };
+        let count = queue.block[0].block[1].value.parse::<usize>().unwrap_or(0);
+        queue.block[0].block[1].value = (count + 1).to_string();
+        let order = Token {
+            proxy: None,
+            token_type: TokenTypes::Number,
+            value: count.to_string(),
+            block: vec![],
+            line_number: 0,
+            row: 0,
+        };
+        queue.block.push(Token {
+            proxy: None,
+            token_type: TokenTypes::List,
+            value: "list".to_string(),
+            block: vec![priority, order, item],
+            line_number: 0,
+            row: 0,
+        });
+
+        // Moves the new entry up the heap past every entry it comes out before
+        let mut n = queue.block.len() - 1;
+        while n > 1 {
+            let parent = n / 2;
+            if queue.queue_cmp(&queue.block[n], &queue.block[parent]) != std::cmp::Ordering::Less {
+                break;
+            }
+            queue.block.swap(n, parent);
+            n = parent;
+        }
+    }
+
+    // Every item at once, for builtins that need to see all of them
+    fn collect_items(&mut self, collection: &Token, i: &Token) -> Vec<Token> {
+        let mut items = self.items(collection, i);
+        let mut all = vec![];
+        while let Some(item) = self.next_item(&mut items, i) {
+            all.push(item)
+        }
+        all
+    }
+
+    // Ranges and generators as the list of their items, for the list
+    // builtins that change a list in place
+    fn as_list(&mut self, collection: Token, i: &Token) -> Token {
+        if !matches!(
+            collection.token_type,
+            TokenTypes::Range | TokenTypes::Generator
+        ) {
+            return collection;
+        }
+        Token {
+            proxy: None,
+            token_type: TokenTypes::List,
+            value: "list".to_string(),
+            block: self.collect_items(&collection, i),
+            line_number: collection.line_number,
+            row: collection.row,
+        }
+    }
+
+    // Handles a.items.0.name: = value, the parser leaves the path whole
+    fn assign_path(&mut self, target: &Token, value: Token) {
+        let path: Vec<&str> = target.value.split('.').collect();
+        if let Some(root) = self.heap.get(path[0]).cloned() {
+            let updated = self.set_field(root, &path[1..], value, target);
+            self.heap.insert(path[0].to_string(), updated);
+        } else {
+            print_error(
+                format!("cannot assign to {}, {} is not defined", target.value, path[0]).as_str(),
+                target.line_number,
+                target.row,
+                &self.file,
+                &self.last_instruction,
+            )
+        }
+    }
+
+    // Returns holder with the value at path replaced, lists are changed
+    // in place and objects are rewritten as their fields
+    fn set_field(
+        &mut self,
+        mut holder: Token,
+        path: &[&str],
+        value: Token,
+        target: &Token,
+    ) -> Token {
+        let (field, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return value,
+        };
+        match holder.token_type {
+            TokenTypes::List => {
+                let index = field.parse().ok().and_then(|n| resolve_index(n, holder.block.len()));
+                let index = match index {
+                    Some(index) => index,
+                    None => {
+                        print_error(
+                            format!(
+                                "cannot assign to {}, {} is not an index of a list with length {}",
+                                target.value,
+                                field,
+                                holder.block.len()
+                            )
+                            .as_str(),
+                            target.line_number,
+                            target.row,
+                            &self.file,
+                            &self.last_instruction,
+                        );
+                        return holder;
+                    }
+                };
+                let item = holder.block[index].clone();
+                holder.block[index] = self.set_field(item, rest, value, target);
+            }
+            TokenTypes::Block => {
+                // The object runs once and its body is swapped for the
+                // fields it ended up with, so writes replace the field
+                // rather than pile up and the body is not run again
+                let mut fields = self.run_object(&holder).heap;
+                if let Some(name) = &holder.proxy {
+                    fields.remove(name);
+                }
+                let item = if rest.is_empty() {
+                    value
+                } else if let Some(current) = fields.get(*field).cloned() {
+                    self.set_field(current, rest, value, target)
+                } else {
+                    let name = holder.proxy.as_ref().unwrap_or(&holder.value);
+                    print_error(
+                        format!("cannot assign to {}, {} has no field {}", target.value, name, field).as_str(),
+                        target.line_number,
+                        target.row,
+                        &self.file,
+                        &self.last_instruction,
+                    );
+                    return holder;
+                };
+                fields.insert(field.to_string(), item);
+
+                let mut names: Vec<String> = fields.keys().cloned().collect();
+                names.sort();
+                let mut body = vec![Token {
+                    proxy: None,
+                    token_type: TokenTypes::Break,
+                    value: "break".to_string(),
+                    block: vec![],
+                    line_number: 0,
+                    row: 0,
+                }];
+                for name in names {
+                    if let Some(value) = fields.remove(&name) {
+                        body.extend(define(&name, without_prefix(value)))
+                    }
+                }
+
+                // Objects stored with = can still use their own name
+                holder.block = match &holder.proxy {
+                    Some(name) => {
+                        let object = Token {
+                            proxy: Some(name.clone()),
+                            token_type: TokenTypes::Block,
+                            value: "block".to_string(),
+                            block: body.clone(),
+                            line_number: 0,
+                            row: 0,
+                        };
+                        let mut prefixed = define(name, object).to_vec();
+                        prefixed.extend(body);
+                        prefixed
+                    }
+                    None => body,
+                };
+            }
+            _ => print_error(
+                format!("cannot assign to {}, {} has no fields", target.value, holder.value).as_str(),
+                target.line_number,
+                target.row,
+                &self.file,
+                &self.last_instruction,
+            ),
+        }
+        holder
+    }
+
     pub fn execute_token(&mut self, i: &Token) {
+        // A name the script bound shadows the builtin of the same name,
+        // blocks are called and anything else is read
+        if i.token_type == TokenTypes::Function {
+            if let Some(bound) = self.heap.get(&i.value).map(|t| t.token_type) {
+                self.execute_token(&Token {
+                    token_type: TokenTypes::Identifier,
+                    ..i.clone()
+                });
+                if bound == TokenTypes::Block {
+                    self.execute_token(&Token {
+                        proxy: None,
+                        token_type: TokenTypes::Symbol,
+                        value: "@".to_string(),
+                        block: vec![],
+                        line_number: i.line_number,
+                        row: i.row,
+                    });
+                }
+                return;
+            }
+        }
         if i.token_type == TokenTypes::Identifier {
             if i.value == "self" {
                 for (key, value) in &self.heap {
@@ -132,7 +848,13 @@ impl ManitcoreVm {
                 return;
             }
 
-            if let Some(tok) = self.heap.get(&i.value) {
+            // Local definitions shadow the ones from the prelude
+            let found = self
+                .heap
+                .get(&i.value)
+                .cloned()
+                .or_else(|| self.modules.borrow().prelude(&i.value));
+            if let Some(tok) = found {
                 self.execution_stack.push(Token {
                     token_type: tok.token_type,
                     value: tok.value.clone(),
@@ -143,13 +865,24 @@ impl ManitcoreVm {
                 });
                 return;
             }
+
+            // Reading a name an import kept private is an error, defining
+            // it again is fine
+            if i.proxy.is_none() {
+                self.check_private(i);
+            }
             self.execution_stack.push(i.clone());
-            //continue;
+            self.last_instruction = i.value.to_owned();
+            return;
         }
 
         // Strings , blocks, list, numbers and bools get pushed
         // onto the execution stack automatically
         if i.token_type == TokenTypes::String {
+            if !i.block.is_empty() {
+                self.interpolate(i);
+                return;
+            }
             self.execution_stack.push(i.clone());
             return;
         }
@@ -171,7 +904,16 @@ impl ManitcoreVm {
             self.execution_stack.push(i.clone());
             return;
         }
-        if i.token_type == TokenTypes::List {
+        if matches!(
+            i.token_type,
+            TokenTypes::List
+                | TokenTypes::Range
+                | TokenTypes::Generator
+                | TokenTypes::Set
+                | TokenTypes::Deque
+                | TokenTypes::Queue
+                | TokenTypes::SortedMap
+        ) {
             self.execution_stack.push(i.clone());
             return;
         }
@@ -214,6 +956,27 @@ impl ManitcoreVm {
                     })
                 }
             }
+            "assert" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    if a.value != "true" {
+                        print_error(
+                            "assertion failed",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
             "break" => {
                 self.exit_loop = true;
             }
@@ -225,6 +988,8 @@ impl ManitcoreVm {
                         cargs.push(arg.value.clone())
                     }
 
+                    // The command runs alongside the script, nothing waits on it
+                    #[allow(clippy::zombie_processes)]
                     Command::new(&b.value)
                         .args(cargs)
                         .spawn()
@@ -233,50 +998,192 @@ impl ManitcoreVm {
             }
             "store_import" => {
                 if let Some(a) = self.execution_stack.pop() {
-                    // Get filename from argument
-                    let mut lexer = lexer::Lexer::new_from_file(&a.value);
-
-                    // Parse the file into tokens
-                    lexer.parse();
-
-                    self.execution_stack.push(Token {
-                        proxy: None,
-                        token_type: TokenTypes::Block,
-                        value: "Block".to_string(),
-                        block: lexer.block_stack[0].clone(),
-                        line_number: 0,
-                        row: 0,
-                    })
-                }
-            }
-            "import" => {
-                if let Some(a) = self.execution_stack.pop() {
-                    let mut lexer = lexer::Lexer::new_from_file(&a.value);
-                    // Parse the file into tokens
-                    lexer.parse();
-
-                    // Create new vm
-                    let mut parser = Parser::new();
-                    if self.debug {
-                        parser.debug = true;
-                    }
+                    // Find the module and get its tokens, read only once
+                    let resolved = self.modules.borrow().resolve(&a.value);
+                    if let Some(path) = resolved {
+                        let tokens = self.modules.borrow_mut().source(&path);
 
-                    // Shunt tokens in vm
-                    let shunted = parser.shunt(&lexer.block_stack[0]).clone();
-                    let mut vm = ManitcoreVm::new(&shunted, &a.value);
-                    if self.debug {
-                        vm.debug = true;
-                    }
-
-                    // Copy the stack and the heap inside the vm
-                    vm.execution_stack = self.execution_stack.clone();
-                    vm.heap = self.heap.clone();
+                        self.execution_stack.push(Token {
+                            proxy: None,
+                            token_type: TokenTypes::Block,
+                            value: "Block".to_string(),
+                            block: tokens,
+                            line_number: 0,
+                            row: 0,
+                        })
+                    } else {
+                        print_error(
+                            format!("could not find module {}", a.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "import" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    let resolved = self.modules.borrow().resolve(&a.value);
+                    if let Some(path) = resolved {
+                        // Modules run once in their own vm, later imports
+                        // reuse whatever the first run defined
+                        let cached = self.modules.borrow().cached(&path);
+                        let module = if let Some(module) = cached {
+                            module
+                        } else {
+                            let entered = self.modules.borrow_mut().enter(&path);
+                            if let Err(cycle) = entered {
+                                print_error(
+                                    &cycle,
+                                    i.line_number,
+                                    i.row,
+                                    &self.file,
+                                    &self.last_instruction,
+                                )
+                            }
+                            let tokens = self.modules.borrow_mut().source(&path);
 
-                    // Run the vm
-                    vm.execute();
+                            // Create new vm
+                            let mut parser = Parser::new();
+                            if self.debug {
+                                parser.debug = true;
+                            }
 
-                    self.heap = vm.heap.clone();
-                    self.execution_stack = vm.execution_stack.clone();
+                            // Shunt tokens in vm
+                            let shunted = parser.shunt(&tokens).clone();
+                            let mut vm = ManitcoreVm::new(&shunted, &path.to_string_lossy());
+                            if self.debug {
+                                vm.debug = true;
+                            }
+                            vm.modules = self.modules.clone();
+
+                            // Run the vm
+                            vm.execute();
+
+                            let module = Module::new(vm.heap, &vm.exports);
+                            self.modules.borrow_mut().leave(&path, module.clone());
+                            module
+                        };
+
+                        for name in module.private {
+                            self.private.insert(name, path.display().to_string());
+                        }
+                        for (name, tok) in module.public {
+                            self.private.remove(&name);
+                            self.heap.insert(name, tok);
+                        }
+                    } else {
+                        print_error(
+                            format!("could not find module {}", a.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "export" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    // Takes a name, a variable or a list of names
+                    let names = if a.token_type == TokenTypes::List {
+                        a.block.iter().map(|t| t.value.clone()).collect()
+                    } else if let Some(p) = a.proxy {
+                        vec![p]
+                    } else {
+                        vec![a.value]
+                    };
+                    self.exports.get_or_insert_with(Vec::new).extend(names);
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "read_file" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    if let Ok(content) = std::fs::read_to_string(&a.value) {
+                        self.execution_stack.push(Token {
+                            proxy: None,
+                            token_type: TokenTypes::String,
+                            value: content,
+                            block: vec![],
+                            line_number: 0,
+                            row: 0,
+                        })
+                    } else {
+                        print_error(
+                            format!("could not read file {}", a.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "write_file" => {
+                if let (Some(content), Some(path)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if std::fs::write(&path.value, &content.value).is_err() {
+                        print_error(
+                            format!("could not write file {}", path.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "file_exists" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Bool,
+                        value: std::path::Path::new(&a.value).is_file().to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
                 } else {
                     print_error(
                         format!("not enough arguments for {}", i.value).as_str(),
@@ -296,6 +1203,9 @@ impl ManitcoreVm {
 
                             // Parse the file into tokens
                             lexer.parse();
+                            if lexer.report_errors() {
+                                std::process::exit(1)
+                            }
 
                             self.execution_stack.push(Token {
                                 proxy: None,
@@ -318,6 +1228,10 @@ impl ManitcoreVm {
 
                             // Parse the file into tokens
                             lexer.parse();
+                            if lexer.report_errors() {
+                                std::process::exit(1)
+                            }
+                            ast::parse_file(&lexer.block_stack[0], &a.value);
                             let mut parser = Parser::new();
                             if self.debug {
                                 parser.debug = true;
@@ -329,6 +1243,7 @@ impl ManitcoreVm {
                             if self.debug {
                                 vm.debug = true;
                             }
+                            vm.modules = self.modules.clone();
 
                             // Copy the stack and the heap inside the vm
                             vm.execution_stack = self.execution_stack.clone();
@@ -352,6 +1267,10 @@ impl ManitcoreVm {
 
                             // Parse the file into tokens
                             lexer.parse();
+                            if lexer.report_errors() {
+                                std::process::exit(1)
+                            }
+                            ast::parse_file(&lexer.block_stack[0], &a.value);
                             let mut parser = Parser::new();
                             if self.debug {
                                 parser.debug = true;
@@ -363,6 +1282,7 @@ impl ManitcoreVm {
                             if self.debug {
                                 vm.debug = true;
                             }
+                            vm.modules = self.modules.clone();
 
                             // Execute the vm using parsed token list
                             vm.execute();
@@ -530,7 +1450,7 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "pow" => {
+            "pow" | "^" => {
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
                     let mut f: f32 = 0.0;
@@ -612,28 +1532,75 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "insert" => {
-                if let (Some(index), Some(item), Some(mut list)) = (
-                    self.execution_stack.pop(),
-                    self.execution_stack.pop(),
-                    self.execution_stack.pop(),
-                ) {
-                    let mut i: usize = 0;
-                    if let Ok(v) = index.value.parse() {
-                        i = v
+            "floor" | "ceil" | "round" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    let mut f: f32 = 0.0;
+
+                    if let Ok(v) = a.value.parse() {
+                        f = v
                     } else {
                         print_error(
                             "expected a number",
-                            index.line_number,
-                            index.row,
+                            i.line_number,
+                            i.row,
                             &self.file,
                             &self.last_instruction,
                         )
                     }
-                    if i > list.block.len() {
-                        list.block.push(item);
+
+                    let result = match i.value.as_str() {
+                        "floor" => f.floor(),
+                        "ceil" => f.ceil(),
+                        _ => f.round(),
+                    };
+
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Number,
+                        value: result.to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "insert" => {
+                if let (Some(index), Some(item), Some(mut list)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    list = self.as_list(list, i);
+                    // Queues, sets and maps decide where their items go
+                    if matches!(list.token_type, TokenTypes::List | TokenTypes::Deque) {
+                        let mut i: usize = 0;
+                        if let Ok(v) = index.value.parse() {
+                            i = v
+                        } else {
+                            print_error(
+                                "expected a number",
+                                index.line_number,
+                                index.row,
+                                &self.file,
+                                &self.last_instruction,
+                            )
+                        }
+                        let start = deque_start(&list);
+                        if start + i > list.block.len() {
+                            list.block.push(item);
+                        } else {
+                            list.block.insert(start + i, item);
+                        }
                     } else {
-                        list.block.insert(i, item);
+                        self.put_item(&mut list, item, false, i);
                     }
                     self.execution_stack.push(list)
                 } else {
@@ -650,22 +1617,47 @@ impl ManitcoreVm {
                 if let (Some(a), Some(mut b)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut i: usize = 0;
-                    if let Ok(v) = a.value.parse() {
-                        i = v
-                    } else {
+                    b = self.as_list(b, i);
+                    // Sets remove the value itself and maps the key rather
+                    // than an index
+                    if b.token_type == TokenTypes::Set {
+                        if let Ok(n) = b.block.binary_search_by(|t| t.set_cmp(&a)) {
+                            b.block.remove(n);
+                        }
+                    } else if b.token_type == TokenTypes::SortedMap {
+                        if let Ok(n) = map_find(&b, &a) {
+                            b.block.remove(n);
+                        }
+                    } else if b.token_type == TokenTypes::Queue {
                         print_error(
-                            "expected a number",
-                            a.line_number,
-                            a.row,
+                            "cannot remove by index from a queue, use pop",
+                            i.line_number,
+                            i.row,
                             &self.file,
                             &self.last_instruction,
                         )
-                    }
-                    if i > b.block.len() {
-                        b.block.pop();
                     } else {
-                        b.block.remove(i);
+                        let mut i: usize = 0;
+                        if let Ok(v) = a.value.parse() {
+                            i = v
+                        } else {
+                            print_error(
+                                "expected a number",
+                                a.line_number,
+                                a.row,
+                                &self.file,
+                                &self.last_instruction,
+                            )
+                        }
+                        // Deques keep free slots in front of their items
+                        let start = deque_start(&b);
+                        if start + i >= b.block.len() {
+                            if b.block.len() > start {
+                                b.block.pop();
+                            }
+                        } else {
+                            b.block.remove(start + i);
+                        }
                     }
 
                     self.execution_stack.push(b)
@@ -683,7 +1675,22 @@ impl ManitcoreVm {
                 if let (Some(mut a), Some(mut b)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    b.block.append(&mut a.block);
+                    a = self.as_list(a, i);
+                    b = self.as_list(b, i);
+                    // Everything but a list takes the items one at a time
+                    // so it stays in order
+                    let items = if a.token_type == TokenTypes::List {
+                        a.block
+                    } else {
+                        self.collect_items(&a, i)
+                    };
+                    if b.token_type == TokenTypes::List {
+                        b.block.extend(items);
+                    } else {
+                        for item in items {
+                            self.put_item(&mut b, item, false, i);
+                        }
+                    }
                     self.execution_stack.push(b)
                 } else {
                     print_error(
@@ -699,7 +1706,8 @@ impl ManitcoreVm {
                 if let (Some(a), Some(mut b)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    b.block.push(a);
+                    b = self.as_list(b, i);
+                    self.put_item(&mut b, a, false, i);
                     self.execution_stack.push(b)
                 } else {
                     print_error(
@@ -711,11 +1719,13 @@ impl ManitcoreVm {
                     )
                 }
             }
-            "pop" => {
+            "pop" | "pop_front" => {
                 if let (Some(a), Some(mut b)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    if let Some(mut item) = b.block.pop() {
+                    b = self.as_list(b, i);
+                    let item = self.take_item(&mut b, i.value == "pop_front");
+                    if let Some(mut item) = item {
                         if item.token_type != TokenTypes::Nothing {
                             if let Some(p) = a.proxy {
                                 item.proxy = Some(p.clone());
@@ -749,38 +1759,66 @@ impl ManitcoreVm {
                 if let (Some(id), Some(block)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
+                    self.check_private(&block);
                     match id.token_type {
                         TokenTypes::Number => {
-                            let mut i: usize = 0;
+                            let mut index: i64 = 0;
                             if let Ok(v) = id.value.parse() {
-                                i = v
+                                index = v
                             } else {
                                 print_error(
-                                    "expected a number",
+                                    "expected a whole number",
                                     id.line_number,
                                     id.row,
                                     &self.file,
                                     &self.last_instruction,
                                 )
                             }
-                            if let Some(item) = block.block.get(i) {
-                                self.execution_stack.push(item.clone())
-                            } else if let Some(name) = block.proxy {
-                                print_error(
-                                    format!("Could not get index from {0} at index: {1}, {0} only has length of {2}  NOTE: list start at index 0. Expected value: 0 to {3}", name,id.value,block.block.len(),block.block.len()-1).as_str(),
+                            // Strings index by character
+                            let chars: Vec<char> = block.value.chars().collect();
+                            let length = self.length_of(&block, &id);
+                            let name = block.proxy.clone().unwrap_or_else(|| {
+                                if block.token_type == TokenTypes::String {
+                                    "STRING".to_string()
+                                } else {
+                                    "BLOCK".to_string()
+                                }
+                            });
+                            match resolve_index(index, length) {
+                                Some(n) if block.token_type == TokenTypes::String => {
+                                    self.execution_stack.push(Token {
+                                        proxy: None,
+                                        token_type: TokenTypes::String,
+                                        value: chars[n].to_string(),
+                                        block: vec![],
+                                        line_number: id.line_number,
+                                        row: id.row,
+                                    })
+                                }
+                                Some(n) if block.token_type == TokenTypes::Range => {
+                                    self.execution_stack.push(range_value(&block, n))
+                                }
+                                Some(n) if block.token_type == TokenTypes::Deque => {
+                                    self.execution_stack.push(block.deque_items()[n].clone())
+                                }
+                                Some(n) if block.token_type == TokenTypes::Queue => {
+                                    self.execution_stack.push(block.queue_items().swap_remove(n))
+                                }
+                                Some(n) => self.execution_stack.push(block.block[n].clone()),
+                                None if length == 0 => print_error(
+                                    format!("Could not get index from {0} at index: {1}, {0} is empty", name, id.value).as_str(),
                                     id.line_number,
                                     id.row,
                                     &self.file,
                                     &self.last_instruction,
-                                )
-                            } else {
-                                print_error(
-                                    format!("Could not get index from BLOCK at index: {0}, BLOCK only has length of {1}  NOTE: list start at index 0. Expected value: 0 to {2}", id.value,block.block.len(),block.block.len()-1).as_str(),
+                                ),
+                                None => print_error(
+                                    format!("Could not get index from {0} at index: {1}, {0} only has length of {2}  NOTE: list start at index 0 and negative indexes count back from the end. Expected value: -{2} to {3}", name, id.value, length, length - 1).as_str(),
                                     id.line_number,
                                     id.row,
                                     &self.file,
                                     &self.last_instruction,
-                                )
+                                ),
                             }
                         }
                         TokenTypes::Identifier => {
@@ -795,6 +1833,7 @@ impl ManitcoreVm {
                                     if self.debug {
                                         vm.debug = true
                                     }
+                                    vm.modules = self.modules.clone();
                                     vm.method_call = true;
                                     vm.execution_stack = self.execution_stack.clone();
                                     // vm.heap = self.heap.clone();
@@ -810,7 +1849,7 @@ impl ManitcoreVm {
                                     }
                                 }
                                 "len" => {
-                                    let length = block.block.len();
+                                    let length = self.length_of(&block, &id);
                                     self.execution_stack.push(Token {
                                         proxy: None,
                                         token_type: TokenTypes::Number,
@@ -821,17 +1860,22 @@ impl ManitcoreVm {
                                     })
                                 }
                                 _ => {
-                                    let mut parser = Parser::new();
-                                    if self.debug {
-                                        parser.debug = true
-                                    }
-                                    let shunted = parser.shunt(&block.block).clone();
-                                    let mut vm = ManitcoreVm::new(&shunted, &block.value);
-                                    if self.debug {
-                                        vm.debug = true
-                                    }
+                                    let vm = self.run_object(&block);
 
-                                    vm.execute();
+                                    // Blocks that export names only expose those
+                                    if let Some(exports) = &vm.exports {
+                                        if !exports.contains(&id.value) {
+                                            let name = block.proxy.as_ref().unwrap_or(&block.value);
+                                            print_error(
+                                                format!("{} is not exported by {}", id.value, name)
+                                                    .as_str(),
+                                                id.line_number,
+                                                id.row,
+                                                &self.file,
+                                                &self.last_instruction,
+                                            )
+                                        }
+                                    }
 
                                     if let Some(tok) = vm.heap.get(&id.value) {
                                         self.execution_stack.push(tok.clone())
@@ -850,6 +1894,7 @@ impl ManitcoreVm {
                                 if self.debug {
                                     vm.debug = true
                                 }
+                                vm.modules = self.modules.clone();
 
                                 vm.method_call = true;
                                 vm.execute();
@@ -880,91 +1925,1036 @@ impl ManitcoreVm {
                 }
             }
             "for" => {
-                if let (Some(block), Some(list), Some(ident)) = (
+                if let (Some(block), Some(collection), Some(ident)) = (
                     self.execution_stack.pop(),
                     self.execution_stack.pop(),
                     self.execution_stack.pop(),
                 ) {
-                    for mut var in list.block {
-                        let mut parser = Parser::new();
-                        if self.debug {
-                            parser.debug = true
-                        }
-                        let mut shunted = parser.shunt(&block.block).clone();
-                        shunted.remove(0);
-                        let mut vm = ManitcoreVm::new(&shunted, &block.value);
-                        if self.debug {
-                            vm.debug = true
-                        }
-                        vm.heap = self.heap.clone();
-
-                        if let Some(p) = &ident.proxy {
-                            var.proxy = Some(p.clone());
-                            vm.heap.insert(p.clone(), var.clone());
-                        } else {
-                            var.proxy = Some(ident.value.clone());
-                            vm.heap.insert(ident.value.clone(), var.clone());
-                        }
+                    let names = self.loop_names(&ident, i);
 
-                        vm.execute();
-                        if vm.exit_loop {
+                    let mut items = self.items(&collection, i);
+                    let pairs = matches!(items, Items::Map(_));
+                    let mut n: usize = 0;
+                    while let Some(item) = self.next_item(&mut items, i) {
+                        let key = Token {
+                            proxy: None,
+                            token_type: TokenTypes::Number,
+                            value: n.to_string(),
+                            block: vec![],
+                            line_number: 0,
+                            row: 0,
+                        };
+                        if !self.loop_body(&block, &names, key, item, pairs) {
                             break;
                         }
-                        self.heap = vm.heap.clone();
+                        n += 1;
                     }
                 }
             }
-            "range" => {
-                if let (Some(end), Some(start)) =
-                    (self.execution_stack.pop(), self.execution_stack.pop())
-                {
-                    let mut s: usize = 0;
-                    let mut e: usize = 0;
+            // [item for x: collection if filter], lowered by the parser
+            "comprehension" => {
+                if let (Some(filter), Some(body), Some(collection), Some(ident)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    let names = self.loop_names(&ident, i);
+                    let mut items = self.items(&collection, i);
+                    let pairs = matches!(items, Items::Map(_));
+                    let mut new_list: Vec<Token> = Vec::new();
+                    let mut n: usize = 0;
+                    while let Some(item) = self.next_item(&mut items, i) {
+                        let key = Token {
+                            proxy: None,
+                            token_type: TokenTypes::Number,
+                            value: n.to_string(),
+                            block: vec![],
+                            line_number: 0,
+                            row: 0,
+                        };
+                        n += 1;
 
-                    if let Ok(v) = start.value.parse() {
-                        s = v
-                    } else {
+                        if filter.token_type == TokenTypes::Block {
+                            let mut vm =
+                                self.loop_vm(&filter, &names, key.clone(), item.clone(), pairs);
+                            vm.execute();
+                            match vm.execution_stack.pop() {
+                                Some(keep) if keep.token_type == TokenTypes::Bool => {
+                                    if keep.value != "true" {
+                                        continue;
+                                    }
+                                }
+                                keep => print_error(
+                                    format!(
+                                        "the if of a list comprehension gave {}, expected a bool",
+                                        keep.map_or("nothing".to_string(), |k| k.value)
+                                    )
+                                    .as_str(),
+                                    i.line_number,
+                                    i.row,
+                                    &self.file,
+                                    &self.last_instruction,
+                                ),
+                            }
+                        }
+
+                        let mut vm = self.loop_vm(&body, &names, key, item, pairs);
+                        vm.execute();
+                        match vm.execution_stack.pop() {
+                            Some(value) if value.token_type != TokenTypes::Break => {
+                                new_list.push(value)
+                            }
+                            _ => print_error(
+                                "a list comprehension item did not produce a value",
+                                i.line_number,
+                                i.row,
+                                &self.file,
+                                &self.last_instruction,
+                            ),
+                        }
+                    }
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: new_list,
+                        line_number: i.line_number,
+                        row: i.row,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "range" | "range_step" => {
+                // range counts by one towards the end, range_step says how far
+                let step = if i.value == "range_step" {
+                    self.execution_stack.pop()
+                } else {
+                    Some(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Nothing,
+                        value: "_".to_string(),
+                        block: vec![],
+                        line_number: i.line_number,
+                        row: i.row,
+                    })
+                };
+                if let (Some(mut step), Some(end), Some(start)) =
+                    (step, self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut bounds: Vec<f64> = vec![];
+                    for bound in [&start, &end] {
+                        if let Ok(v) = bound.value.parse() {
+                            bounds.push(v)
+                        } else {
+                            print_error(
+                                format!("expected a number, got {}", bound.value).as_str(),
+                                bound.line_number,
+                                bound.row,
+                                &self.file,
+                                &self.last_instruction,
+                            )
+                        }
+                    }
+                    if step.token_type == TokenTypes::Nothing {
+                        step.token_type = TokenTypes::Number;
+                        step.value = if bounds[1] < bounds[0] { "-1" } else { "1" }.to_string();
+                    }
+                    match step.value.parse::<f64>() {
+                        Ok(v) if v != 0.0 && v.is_finite() => {}
+                        _ => print_error(
+                            format!("range step has to be a number other than 0, got {}", step.value)
+                                .as_str(),
+                            step.line_number,
+                            step.row,
+                            &self.file,
+                            &self.last_instruction,
+                        ),
+                    }
+
+                    // Nothing is made until the range is walked over
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Range,
+                        value: "range".to_string(),
+                        block: vec![start, end, step],
+                        line_number: i.line_number,
+                        row: i.row,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "generate" => {
+                if let (Some(block), Some(seed)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if block.token_type != TokenTypes::Block {
                         print_error(
-                            "expected a number",
+                            format!("generate expected a block, got {}", block.value).as_str(),
                             i.line_number,
                             i.row,
                             &self.file,
                             &self.last_instruction,
                         )
                     }
-                    if let Ok(v) = end.value.parse() {
-                        e = v
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Generator,
+                        value: "generator".to_string(),
+                        block: vec![seed, block],
+                        line_number: i.line_number,
+                        row: i.row,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "collect" => {
+                if let Some(collection) = self.execution_stack.pop() {
+                    let items = self.collect_items(&collection, i);
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: items,
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "set_of" => {
+                if let Some(collection) = self.execution_stack.pop() {
+                    let items = self.collect_items(&collection, i);
+                    self.execution_stack.push(set_of(items, i.line_number, i.row))
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "add" => {
+                if let (Some(item), Some(mut set)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if set.token_type != TokenTypes::Set {
+                        print_error(
+                            format!("add expected a set, got {}", set.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    if let Err(n) = set.block.binary_search_by(|t| t.set_cmp(&item)) {
+                        set.block.insert(n, item)
+                    }
+                    self.execution_stack.push(set)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "contains" => {
+                if let (Some(item), Some(collection)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    // Strings look for the text, maps and sets look the item
+                    // up, anything else is walked over until an equal item
+                    // turns up
+                    let found = if collection.token_type == TokenTypes::String {
+                        collection.value.contains(&item.value)
+                    } else if collection.token_type == TokenTypes::SortedMap {
+                        map_find(&collection, &item).is_ok()
+                    } else if collection.token_type == TokenTypes::Set {
+                        collection
+                            .block
+                            .binary_search_by(|t| t.set_cmp(&item))
+                            .is_ok()
+                    } else {
+                        let mut items = self.items(&collection, i);
+                        let mut found = false;
+                        while let Some(t) = self.next_item(&mut items, i) {
+                            if t.equals(&item) {
+                                found = true;
+                                break;
+                            }
+                        }
+                        found
+                    };
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Bool,
+                        value: found.to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "deque" | "sorted_map" => {
+                if let Some(collection) = self.execution_stack.pop() {
+                    let items = self.collect_items(&collection, i);
+                    let mut result = Token {
+                        proxy: None,
+                        token_type: TokenTypes::Deque,
+                        value: i.value.clone(),
+                        block: vec![],
+                        line_number: i.line_number,
+                        row: i.row,
+                    };
+                    if i.value == "deque" {
+                        // No free slots in front to start with
+                        result.block = vec![Token {
+                            proxy: None,
+                            token_type: TokenTypes::Number,
+                            value: "0".to_string(),
+                            block: vec![],
+                            line_number: 0,
+                            row: 0,
+                        }];
+                        result.block.extend(items)
+                    } else {
+                        // Maps are made from [key value] pairs, later keys win
+                        result.token_type = TokenTypes::SortedMap;
+                        for pair in items {
+                            if pair.token_type != TokenTypes::List || pair.block.len() != 2 {
+                                print_error(
+                                    format!(
+                                        "sorted_map expected [key value] pairs, got {}",
+                                        pair.display()
+                                    )
+                                    .as_str(),
+                                    i.line_number,
+                                    i.row,
+                                    &self.file,
+                                    &self.last_instruction,
+                                );
+                                continue;
+                            }
+                            let mut pair = pair.block.into_iter();
+                            if let (Some(key), Some(value)) = (pair.next(), pair.next()) {
+                                map_put(&mut result, key, value)
+                            }
+                        }
+                    }
+                    self.execution_stack.push(result)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "min_queue" | "max_queue" => {
+                // The key block gives the priority of an item, _ uses the item
+                if let (Some(key), Some(collection)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if !matches!(key.token_type, TokenTypes::Block | TokenTypes::Nothing) {
+                        print_error(
+                            format!("{} expected a key block or _, got {}", i.value, key.value)
+                                .as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    let items = self.collect_items(&collection, i);
+                    let count = Token {
+                        proxy: None,
+                        token_type: TokenTypes::Number,
+                        value: "0".to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    };
+                    let mut queue = Token {
+                        proxy: None,
+                        token_type: TokenTypes::Queue,
+                        value: i.value.clone(),
+                        block: vec![Token {
+                            proxy: None,
+                            token_type: TokenTypes::List,
+                            value: "list".to_string(),
+                            block: vec![key, count],
+                            line_number: 0,
+                            row: 0,
+                        }],
+                        line_number: i.line_number,
+                        row: i.row,
+                    };
+                    for item in items {
+                        self.queue_push(&mut queue, item, i)
+                    }
+                    self.execution_stack.push(queue)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "push_front" => {
+                if let (Some(a), Some(mut b)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    b = self.as_list(b, i);
+                    self.put_item(&mut b, a, true, i);
+                    self.execution_stack.push(b)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "peek" => {
+                // The item that pop_front or a queue's pop would take next
+                if let Some(collection) = self.execution_stack.pop() {
+                    let next = match collection.token_type {
+                        TokenTypes::Queue => collection.block.get(1).map(|entry| &entry.block[2]),
+                        TokenTypes::Deque => collection.deque_items().first(),
+                        _ => collection.block.first(),
+                    };
+                    let item = next.cloned().unwrap_or(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Nothing,
+                        value: "_".to_string(),
+                        block: vec![],
+                        line_number: i.line_number,
+                        row: i.row,
+                    });
+                    self.execution_stack.push(item)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "put" => {
+                if let (Some(value), Some(key), Some(mut map)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    if map.token_type == TokenTypes::SortedMap {
+                        map_put(&mut map, key, value)
+                    } else {
+                        print_error(
+                            format!("put expected a sorted map, got {}", map.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    self.execution_stack.push(map)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "get" => {
+                // Keys that are not in the map give _
+                if let (Some(key), Some(map)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if map.token_type != TokenTypes::SortedMap {
+                        print_error(
+                            format!("get expected a sorted map, got {}", map.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    let value = match map_find(&map, &key) {
+                        Ok(n) => map.block[n].block[1].clone(),
+                        Err(_) => Token {
+                            proxy: None,
+                            token_type: TokenTypes::Nothing,
+                            value: "_".to_string(),
+                            block: vec![],
+                            line_number: i.line_number,
+                            row: i.row,
+                        },
+                    };
+                    self.execution_stack.push(value)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "union" | "intersection" | "difference" => {
+                if let (Some(b), Some(a)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut left = self.collect_items(&a, i);
+                    let right = self.collect_items(&b, i);
+                    let keys: HashSet<String> = right.iter().map(|t| t.hash_key()).collect();
+                    match i.value.as_str() {
+                        "union" => left.extend(right),
+                        "intersection" => left.retain(|t| keys.contains(&t.hash_key())),
+                        _ => left.retain(|t| !keys.contains(&t.hash_key())),
+                    }
+                    self.execution_stack.push(set_of(left, i.line_number, i.row))
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "slice" | "slice_step" => {
+                // slice takes every item, slice_step says how far to move
+                let step = if i.value == "slice_step" {
+                    self.execution_stack.pop()
+                } else {
+                    Some(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Number,
+                        value: "1".to_string(),
+                        block: vec![],
+                        line_number: i.line_number,
+                        row: i.row,
+                    })
+                };
+                if let (Some(step), Some(end), Some(start), Some(target)) = (
+                    step,
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    // _ leaves a bound open
+                    let mut bounds: Vec<Option<i64>> = vec![];
+                    for bound in [&start, &end, &step] {
+                        if bound.token_type == TokenTypes::Nothing {
+                            bounds.push(None)
+                        } else if let Ok(v) = bound.value.parse() {
+                            bounds.push(Some(v))
+                        } else {
+                            print_error(
+                                format!("expected a whole number or _, got {}", bound.value).as_str(),
+                                bound.line_number,
+                                bound.row,
+                                &self.file,
+                                &self.last_instruction,
+                            )
+                        }
+                    }
+                    let step = bounds[2].unwrap_or(1);
+                    if step == 0 {
+                        print_error(
+                            "slice step cannot be zero",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    match target.token_type {
+                        TokenTypes::String => {
+                            let chars: Vec<char> = target.value.chars().collect();
+                            let indexes = slice_indexes(chars.len(), bounds[0], bounds[1], step);
+                            self.execution_stack.push(Token {
+                                proxy: None,
+                                token_type: TokenTypes::String,
+                                value: indexes.into_iter().map(|n| chars[n]).collect(),
+                                block: vec![],
+                                line_number: 0,
+                                row: 0,
+                            })
+                        }
+                        TokenTypes::List => {
+                            let indexes =
+                                slice_indexes(target.block.len(), bounds[0], bounds[1], step);
+                            self.execution_stack.push(Token {
+                                proxy: None,
+                                token_type: TokenTypes::List,
+                                value: "list".to_string(),
+                                block: indexes.into_iter().map(|n| target.block[n].clone()).collect(),
+                                line_number: 0,
+                                row: 0,
+                            })
+                        }
+                        _ => print_error(
+                            format!("cannot slice {}, expected a list or string", target.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        ),
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "map" | "filter" | "any" | "all" | "reduce" => {
+                if let (Some(block), Some(list)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut items = self.items(&list, i);
+                    let result = match i.value.as_str() {
+                        "map" => {
+                            let mut new_list: Vec<Token> = Vec::new();
+                            while let Some(item) = self.next_item(&mut items, i) {
+                                new_list.push(self.call_block(&block, vec![item], i))
+                            }
+                            Token {
+                                proxy: None,
+                                token_type: TokenTypes::List,
+                                value: "list".to_string(),
+                                block: new_list,
+                                line_number: 0,
+                                row: 0,
+                            }
+                        }
+                        "filter" => {
+                            let mut new_list: Vec<Token> = Vec::new();
+                            while let Some(item) = self.next_item(&mut items, i) {
+                                let keep = self.call_block(&block, vec![item.clone()], i);
+                                if self.expect_bool(&keep, i) {
+                                    new_list.push(item)
+                                }
+                            }
+                            Token {
+                                proxy: None,
+                                token_type: TokenTypes::List,
+                                value: "list".to_string(),
+                                block: new_list,
+                                line_number: 0,
+                                row: 0,
+                            }
+                        }
+                        "reduce" => {
+                            let mut total = match self.next_item(&mut items, i) {
+                                Some(first) => first,
+                                None => {
+                                    print_error(
+                                        "cannot reduce an empty list, use fold to give a starting value",
+                                        i.line_number,
+                                        i.row,
+                                        &self.file,
+                                        &self.last_instruction,
+                                    );
+                                    return;
+                                }
+                            };
+                            while let Some(item) = self.next_item(&mut items, i) {
+                                total = self.call_block(&block, vec![total, item], i)
+                            }
+                            total
+                        }
+                        // any stops at the first true, all at the first false
+                        _ => {
+                            let wanted = i.value == "any";
+                            let mut found = !wanted;
+                            while let Some(item) = self.next_item(&mut items, i) {
+                                let answer = self.call_block(&block, vec![item], i);
+                                if self.expect_bool(&answer, i) == wanted {
+                                    found = wanted;
+                                    break;
+                                }
+                            }
+                            Token {
+                                proxy: None,
+                                token_type: TokenTypes::Bool,
+                                value: found.to_string(),
+                                block: vec![],
+                                line_number: 0,
+                                row: 0,
+                            }
+                        }
+                    };
+                    self.execution_stack.push(result)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "sort" => {
+                if let Some(list) = self.execution_stack.pop() {
+                    let all = self.collect_items(&list, i);
+                    let sorted = merge_sort(all, &mut |a, b| a.total_cmp(b));
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: sorted,
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "sort_by" | "sort_with" => {
+                if let (Some(block), Some(list)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let all = self.collect_items(&list, i);
+                    let sorted = if i.value == "sort_by" {
+                        // Each key is worked out once, then items are
+                        // ordered by their keys
+                        let mut keyed: Vec<Token> = Vec::new();
+                        for item in all {
+                            let key = self.call_block(&block, vec![item.clone()], i);
+                            keyed.push(Token {
+                                proxy: None,
+                                token_type: TokenTypes::List,
+                                value: "list".to_string(),
+                                block: vec![key, item],
+                                line_number: 0,
+                                row: 0,
+                            })
+                        }
+                        merge_sort(keyed, &mut |a, b| a.block[0].total_cmp(&b.block[0]))
+                            .into_iter()
+                            .map(|mut pair| pair.block.remove(1))
+                            .collect()
+                    } else {
+                        // The comparator answers below zero when a goes
+                        // first, zero when they tie and above zero otherwise
+                        merge_sort(all, &mut |a, b| {
+                            let answer = self.call_block(&block, vec![a.clone(), b.clone()], i);
+                            match answer.value.parse::<f32>() {
+                                Ok(n) if answer.token_type == TokenTypes::Number => {
+                                    n.partial_cmp(&0.0).unwrap_or(std::cmp::Ordering::Equal)
+                                }
+                                _ => {
+                                    print_error(
+                                        format!(
+                                            "the block given to {} returned {}, expected a number",
+                                            i.value, answer.value
+                                        )
+                                        .as_str(),
+                                        i.line_number,
+                                        i.row,
+                                        &self.file,
+                                        &self.last_instruction,
+                                    );
+                                    std::cmp::Ordering::Equal
+                                }
+                            }
+                        })
+                    };
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: sorted,
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "fold" => {
+                if let (Some(block), Some(start), Some(list)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    let mut items = self.items(&list, i);
+                    let mut total = start;
+                    while let Some(item) = self.next_item(&mut items, i) {
+                        total = self.call_block(&block, vec![total, item], i)
+                    }
+                    self.execution_stack.push(total)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            // find on a list with a block, the string find is further down
+            "find"
+                if self
+                    .execution_stack
+                    .last()
+                    .is_some_and(|t| t.token_type == TokenTypes::Block) =>
+            {
+                if let (Some(block), Some(list)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut items = self.items(&list, i);
+                    let mut found = Token {
+                        proxy: None,
+                        token_type: TokenTypes::Nothing,
+                        value: "_".to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    };
+                    while let Some(item) = self.next_item(&mut items, i) {
+                        let answer = self.call_block(&block, vec![item.clone()], i);
+                        if self.expect_bool(&answer, i) {
+                            found = item;
+                            break;
+                        }
+                    }
+                    self.execution_stack.push(found)
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "zip" => {
+                if let (Some(b), Some(a)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    // Pairs stop at the end of the shorter one, so a
+                    // generator can be zipped with a list
+                    let mut left = self.items(&a, i);
+                    let mut right = self.items(&b, i);
+                    let mut pairs: Vec<Token> = Vec::new();
+                    while let Some(x) = self.next_item(&mut left, i) {
+                        let y = match self.next_item(&mut right, i) {
+                            Some(y) => y,
+                            None => break,
+                        };
+                        pairs.push(Token {
+                            proxy: None,
+                            token_type: TokenTypes::List,
+                            value: "list".to_string(),
+                            block: vec![x, y],
+                            line_number: 0,
+                            row: 0,
+                        })
+                    }
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: pairs,
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "enumerate" | "flatten" => {
+                if let Some(list) = self.execution_stack.pop() {
+                    let all = self.collect_items(&list, i);
+                    let mut new_list: Vec<Token> = Vec::new();
+                    for (n, item) in all.into_iter().enumerate() {
+                        if i.value == "enumerate" {
+                            new_list.push(Token {
+                                proxy: None,
+                                token_type: TokenTypes::List,
+                                value: "list".to_string(),
+                                block: vec![
+                                    Token {
+                                        proxy: None,
+                                        token_type: TokenTypes::Number,
+                                        value: n.to_string(),
+                                        block: vec![],
+                                        line_number: 0,
+                                        row: 0,
+                                    },
+                                    item,
+                                ],
+                                line_number: 0,
+                                row: 0,
+                            })
+                        } else if item.token_type == TokenTypes::List {
+                            // Only one level is taken apart
+                            new_list.extend(item.block)
+                        } else {
+                            new_list.push(item)
+                        }
+                    }
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: new_list,
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "take" | "drop" => {
+                if let (Some(count), Some(list)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut n: usize = 0;
+                    if let Ok(v) = count.value.parse() {
+                        n = v
                     } else {
                         print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
+                            "expected a positive whole number",
+                            count.line_number,
+                            count.row,
                             &self.file,
                             &self.last_instruction,
                         )
                     }
-
-                    let mut new_list: Vec<Token> = Vec::new();
-
-                    for x in s..=e {
-                        new_list.push(Token {
+                    // Only as many items as needed are pulled, counts past
+                    // the end take or drop everything
+                    let mut items = self.items(&list, i);
+                    let mut taken: Vec<Token> = Vec::new();
+                    while taken.len() < n {
+                        match self.next_item(&mut items, i) {
+                            Some(item) => taken.push(item),
+                            None => break,
+                        }
+                    }
+                    if i.value == "drop" && list.token_type == TokenTypes::Generator {
+                        // A generator may never end, dropping from one gives
+                        // a generator that starts further on
+                        let seed = self.next_item(&mut items, i).unwrap_or(Token {
                             proxy: None,
-                            token_type: TokenTypes::Number,
-                            value: x.to_string(),
+                            token_type: TokenTypes::Nothing,
+                            value: "_".to_string(),
                             block: vec![],
+                            line_number: i.line_number,
+                            row: i.row,
+                        });
+                        self.execution_stack.push(Token {
+                            proxy: None,
+                            token_type: TokenTypes::Generator,
+                            value: "generator".to_string(),
+                            block: vec![seed, list.block[1].clone()],
+                            line_number: i.line_number,
+                            row: i.row,
+                        })
+                    } else {
+                        if i.value == "drop" {
+                            taken.clear();
+                            while let Some(item) = self.next_item(&mut items, i) {
+                                taken.push(item)
+                            }
+                        }
+                        self.execution_stack.push(Token {
+                            proxy: None,
+                            token_type: TokenTypes::List,
+                            value: "list".to_string(),
+                            block: taken,
                             line_number: 0,
                             row: 0,
                         })
                     }
-
-                    self.execution_stack.push(Token {
-                        proxy: None,
-                        token_type: TokenTypes::List,
-                        value: "list".to_string(),
-                        block: new_list.clone(),
-                        line_number: 0,
-                        row: 0,
-                    })
                 } else {
                     print_error(
                         format!("not enough arguments for {}", i.value).as_str(),
@@ -1042,10 +3032,8 @@ impl ManitcoreVm {
                     }
                 }
             }
-            "?" => {
-                if self.method_call {
-                    self.exit_loop = true;
-                }
+            "?" if self.method_call => {
+                self.exit_loop = true;
             }
             // This function will pop off a block and execute it using the outer scope heap and stack
             "call" => {
@@ -1062,6 +3050,7 @@ impl ManitcoreVm {
                     if self.debug {
                         vm.debug = true;
                     }
+                    vm.modules = self.modules.clone();
 
                     // Copy the stack and the heap inside the vm
                     vm.execution_stack = self.execution_stack.clone();
@@ -1179,6 +3168,8 @@ impl ManitcoreVm {
             // This function will pop off a block and execute it using the outer scope heap and stack
             "@" => {
                 if let Some(a) = self.execution_stack.pop() {
+                    self.check_private(&a);
+
                     // Create new vm
                     let mut parser = Parser::new();
                     if self.debug {
@@ -1191,6 +3182,7 @@ impl ManitcoreVm {
                     if self.debug {
                         vm.debug = true;
                     }
+                    vm.modules = self.modules.clone();
 
                     // Copy the stack and the heap inside the vm
                     vm.execution_stack = self.execution_stack.clone();
@@ -1296,7 +3288,12 @@ impl ManitcoreVm {
                 if let (Some(mut a), Some(b)) =
                     (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    if b.token_type == TokenTypes::Identifier || b.proxy.is_some() {
+                    if b.token_type == TokenTypes::Identifier
+                        && b.proxy.is_none()
+                        && b.value.contains('.')
+                    {
+                        self.assign_path(&b, a);
+                    } else if b.token_type == TokenTypes::Identifier || b.proxy.is_some() {
                         match a.token_type {
                             TokenTypes::Block => {
                                 if let Some(p) = b.proxy {
@@ -1338,9 +3335,9 @@ impl ManitcoreVm {
                                     ];
                                     recurse.append(&mut a.block);
                                     self.heap.insert(
-                                        p,
+                                        p.clone(),
                                         Token {
-                                            proxy: Some(b.value),
+                                            proxy: Some(p),
                                             token_type: TokenTypes::Block,
                                             value: "block".to_string(),
                                             block: recurse.clone(),
@@ -1411,13 +3408,292 @@ impl ManitcoreVm {
                         }
                     } else {
                         print_error(
-                            "expected an identifier",
+                            "expected an identifier",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "lss" => {
+                // todo: does not support blocks atm
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
+
+                    if let Ok(v) = a.value.parse() {
+                        f = v
+                    } else {
+                        print_error(
+                            "expected a normal token",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    if let Ok(v) = b.value.parse() {
+                        s = v
+                    } else {
+                        print_error(
+                            "expected a normal token",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: (s < f).to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "gtr" => {
+                // todo: does not support blocks atm
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
+
+                    if let Ok(v) = a.value.parse() {
+                        f = v
+                    } else {
+                        print_error(
+                            "expected a normal token",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    if let Ok(v) = b.value.parse() {
+                        s = v
+                    } else {
+                        print_error(
+                            "expected a normal token",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: (s > f).to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "==" | "!=" => {
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let equal = b.equals(&a);
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: (equal == (i.value == "==")).to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "<" | ">" | "<=" | ">=" => {
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    if let Some(ordering) = b.compare(&a) {
+                        let result = match i.value.as_str() {
+                            "<" => ordering.is_lt(),
+                            ">" => ordering.is_gt(),
+                            "<=" => ordering.is_le(),
+                            _ => ordering.is_ge(),
+                        };
+                        self.execution_stack.push(Token {
+                            token_type: TokenTypes::Bool,
+                            value: result.to_string(),
+                            line_number: 0,
+                            row: 0,
+                            block: vec![],
+                            proxy: None,
+                        })
+                    } else {
+                        print_error(
+                            format!("cannot compare {} with {}", b.value, a.value).as_str(),
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            // Same test as ==
+            "equ" => {
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: b.equals(&a).to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "not" | "!" => {
+                // todo: does not support blocks atm
+                if let Some(a) = self.execution_stack.pop() {
+                    let mut f: bool = false;
+
+                    if let Ok(v) = a.value.parse() {
+                        f = v
+                    } else {
+                        print_error(
+                            "expected a normal token",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: (!f).to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            // The right side was kept aside by the parser and only
+            // runs when the left side doesn't already decide the result
+            "&&" | "||" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    let mut f: bool = false;
+
+                    if let Ok(v) = a.value.parse() {
+                        f = v
+                    } else {
+                        print_error(
+                            "expected a bool",
                             i.line_number,
                             i.row,
                             &self.file,
                             &self.last_instruction,
                         )
                     }
+
+                    if f == (i.value == "&&") {
+                        let depth = self.execution_stack.len();
+                        for t in &i.block {
+                            self.execute_token(t)
+                        }
+                        if self.execution_stack.len() > depth {
+                            if let Some(b) = self.execution_stack.pop() {
+                                if let Ok(v) = b.value.parse() {
+                                    f = v
+                                } else {
+                                    print_error(
+                                        "expected a bool",
+                                        i.line_number,
+                                        i.row,
+                                        &self.file,
+                                        &self.last_instruction,
+                                    )
+                                }
+                            }
+                        } else {
+                            print_error(
+                                format!("not enough arguments for {}", i.value).as_str(),
+                                i.line_number,
+                                i.row,
+                                &self.file,
+                                &self.last_instruction,
+                            )
+                        }
+                    }
+
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Bool,
+                        value: f.to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
                 } else {
                     print_error(
                         format!("not enough arguments for {}", i.value).as_str(),
@@ -1428,12 +3704,12 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "lss" => {
+            "and" => {
                 // todo: does not support blocks atm
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: f32 = 0.0;
-                    let mut s: f32 = 0.0;
+                    let mut f: bool = false;
+                    let mut s: bool = false;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
@@ -1461,7 +3737,7 @@ impl ManitcoreVm {
 
                     self.execution_stack.push(Token {
                         token_type: TokenTypes::Bool,
-                        value: (s < f).to_string(),
+                        value: (f && s).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1477,12 +3753,12 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "gtr" => {
+            "or" => {
                 // todo: does not support blocks atm
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: f32 = 0.0;
-                    let mut s: f32 = 0.0;
+                    let mut f: bool = false;
+                    let mut s: bool = false;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
@@ -1507,9 +3783,10 @@ impl ManitcoreVm {
                             &self.last_instruction,
                         )
                     }
+
                     self.execution_stack.push(Token {
                         token_type: TokenTypes::Bool,
-                        value: (s > f).to_string(),
+                        value: (f || s).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1525,18 +3802,17 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "equ" => {
-                // todo: does not support blocks atm
+            "+" => {
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: String = String::new();
-                    let mut s: String = String::new();
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1548,7 +3824,7 @@ impl ManitcoreVm {
                         s = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1557,8 +3833,8 @@ impl ManitcoreVm {
                     }
 
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Bool,
-                        value: (f == s).to_string(),
+                        token_type: TokenTypes::Number,
+                        value: (f + s).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1574,16 +3850,29 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "not" => {
-                // todo: does not support blocks atm
-                if let Some(a) = self.execution_stack.pop() {
-                    let mut f: bool = false;
+            "-" => {
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    if let Ok(v) = b.value.parse() {
+                        s = v
+                    } else {
+                        print_error(
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1592,8 +3881,8 @@ impl ManitcoreVm {
                     }
 
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Bool,
-                        value: (!f).to_string(),
+                        token_type: TokenTypes::Number,
+                        value: (s - f).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1609,30 +3898,30 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "and" => {
-                // todo: does not support blocks atm
+            "*" => {
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: bool = false;
-                    let mut s: bool = false;
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
                             &self.last_instruction,
-                        )
+                        );
+                        println!("{}", a.value)
                     }
 
                     if let Ok(v) = b.value.parse() {
                         s = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1641,8 +3930,8 @@ impl ManitcoreVm {
                     }
 
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Bool,
-                        value: (f && s).to_string(),
+                        token_type: TokenTypes::Number,
+                        value: (f * s).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1658,18 +3947,17 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "or" => {
-                // todo: does not support blocks atm
+            "/" => {
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: bool = false;
-                    let mut s: bool = false;
+                    let mut f: f32 = 0.0;
+                    let mut s: f32 = 0.0;
 
                     if let Ok(v) = a.value.parse() {
                         f = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1681,7 +3969,7 @@ impl ManitcoreVm {
                         s = v
                     } else {
                         print_error(
-                            "expected a normal token",
+                            "expected a number",
                             i.line_number,
                             i.row,
                             &self.file,
@@ -1690,8 +3978,8 @@ impl ManitcoreVm {
                     }
 
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Bool,
-                        value: (f || s).to_string(),
+                        token_type: TokenTypes::Number,
+                        value: (s / f).to_string(),
                         line_number: 0,
                         row: 0,
                         block: vec![],
@@ -1707,7 +3995,9 @@ impl ManitcoreVm {
                     )
                 };
             }
-            "+" => {
+            // Remainder keeps the sign of the left side, integer
+            // division rounds down
+            "%" | "//" => {
                 if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
                 {
                     let mut f: f32 = 0.0;
@@ -1737,13 +4027,175 @@ impl ManitcoreVm {
                         )
                     }
 
+                    if f == 0.0 {
+                        print_error(
+                            "division by zero",
+                            i.line_number,
+                            i.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    let result = if i.value == "%" { s % f } else { (s / f).floor() };
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::Number,
+                        value: result.to_string(),
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            "concat" => {
+                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    let f: String = a.value;
+                    let s: String = b.value;
+
+                    self.execution_stack.push(Token {
+                        token_type: TokenTypes::String,
+                        value: s + &f,
+                        line_number: 0,
+                        row: 0,
+                        block: vec![],
+                        proxy: None,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                };
+            }
+            // String functions work on unicode scalar values, not bytes
+            "len" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    let length = self.length_of(&a, i);
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Number,
+                        value: length.to_string(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "substr" => {
+                if let (Some(end), Some(start), Some(text)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
+                    self.expect_string(&text, i);
+                    let mut s: usize = 0;
+                    if let Ok(v) = start.value.parse() {
+                        s = v
+                    } else {
+                        print_error(
+                            "expected a positive whole number",
+                            start.line_number,
+                            start.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+                    let mut e: usize = 0;
+                    if let Ok(v) = end.value.parse() {
+                        e = v
+                    } else {
+                        print_error(
+                            "expected a positive whole number",
+                            end.line_number,
+                            end.row,
+                            &self.file,
+                            &self.last_instruction,
+                        )
+                    }
+
+                    // Out of range bounds are clamped to the string
+                    let chars: Vec<char> = text.value.chars().collect();
+                    let e = e.min(chars.len());
+                    let s = s.min(e);
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::String,
+                        value: chars[s..e].iter().collect(),
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "find" => {
+                if let (Some(needle), Some(text)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
+                {
+                    // Index in characters of the first match, or -1
+                    let index = match text.value.find(&needle.value) {
+                        Some(byte) => text.value[..byte].chars().count().to_string(),
+                        None => "-1".to_string(),
+                    };
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::Number,
+                        value: index,
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "replace" => {
+                if let (Some(to), Some(from), Some(text)) = (
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                    self.execution_stack.pop(),
+                ) {
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Number,
-                        value: (f + s).to_string(),
+                        proxy: None,
+                        token_type: TokenTypes::String,
+                        value: text.value.replace(&from.value, &to.value),
+                        block: vec![],
                         line_number: 0,
                         row: 0,
-                        block: vec![],
-                        proxy: None,
                     })
                 } else {
                     print_error(
@@ -1753,45 +4205,39 @@ impl ManitcoreVm {
                         &self.file,
                         &self.last_instruction,
                     )
-                };
+                }
             }
-            "-" => {
-                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+            "split" => {
+                if let (Some(separator), Some(text)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: f32 = 0.0;
-                    let mut s: f32 = 0.0;
-
-                    if let Ok(v) = a.value.parse() {
-                        f = v
-                    } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
-
-                    if let Ok(v) = b.value.parse() {
-                        s = v
+                    // An empty separator splits into characters
+                    let parts: Vec<String> = if separator.value.is_empty() {
+                        text.value.chars().map(|c| c.to_string()).collect()
                     } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
+                        text.value
+                            .split(separator.value.as_str())
+                            .map(|part| part.to_string())
+                            .collect()
+                    };
 
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Number,
-                        value: (s - f).to_string(),
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: parts
+                            .into_iter()
+                            .map(|part| Token {
+                                proxy: None,
+                                token_type: TokenTypes::String,
+                                value: part,
+                                block: vec![],
+                                line_number: 0,
+                                row: 0,
+                            })
+                            .collect(),
                         line_number: 0,
                         row: 0,
-                        block: vec![],
-                        proxy: None,
                     })
                 } else {
                     print_error(
@@ -1801,46 +4247,95 @@ impl ManitcoreVm {
                         &self.file,
                         &self.last_instruction,
                     )
-                };
+                }
             }
-            "*" => {
-                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+            "join" => {
+                if let (Some(separator), Some(list)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: f32 = 0.0;
-                    let mut s: f32 = 0.0;
-
-                    if let Ok(v) = a.value.parse() {
-                        f = v
-                    } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        );
-                        println!("{}", a.value)
-                    }
-
-                    if let Ok(v) = b.value.parse() {
-                        s = v
+                    // Deques and queues keep more than their items in the block
+                    let items = if matches!(
+                        list.token_type,
+                        TokenTypes::Deque | TokenTypes::Queue | TokenTypes::Range | TokenTypes::Generator
+                    ) {
+                        self.collect_items(&list, i)
                     } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
-
+                        list.block
+                    };
+                    let parts: Vec<String> = items.iter().map(|t| t.display()).collect();
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Number,
-                        value: (f * s).to_string(),
+                        proxy: None,
+                        token_type: TokenTypes::String,
+                        value: parts.join(&separator.value),
+                        block: vec![],
                         line_number: 0,
                         row: 0,
-                        block: vec![],
+                    })
+                } else {
+                    print_error(
+                        format!("not enough arguments for {}", i.value).as_str(),
+                        i.line_number,
+                        i.row,
+                        &self.file,
+                        &self.last_instruction,
+                    )
+                }
+            }
+            "reverse"
+                if self
+                    .execution_stack
+                    .last()
+                    .is_some_and(|t| t.token_type == TokenTypes::List) =>
+            {
+                if let Some(mut list) = self.execution_stack.pop() {
+                    list.block.reverse();
+                    list.proxy = None;
+                    self.execution_stack.push(list)
+                }
+            }
+            // Other collections give back their items as a reversed list
+            "reverse"
+                if self.execution_stack.last().is_some_and(|t| {
+                    matches!(
+                        t.token_type,
+                        TokenTypes::Range
+                            | TokenTypes::Generator
+                            | TokenTypes::Deque
+                            | TokenTypes::Queue
+                            | TokenTypes::Set
+                            | TokenTypes::SortedMap
+                    )
+                }) =>
+            {
+                if let Some(collection) = self.execution_stack.pop() {
+                    let mut items = self.collect_items(&collection, i);
+                    items.reverse();
+                    self.execution_stack.push(Token {
+                        proxy: None,
+                        token_type: TokenTypes::List,
+                        value: "list".to_string(),
+                        block: items,
+                        line_number: 0,
+                        row: 0,
+                    })
+                }
+            }
+            "upper" | "lower" | "trim" | "reverse" => {
+                if let Some(a) = self.execution_stack.pop() {
+                    self.expect_string(&a, i);
+                    let value = match i.value.as_str() {
+                        "upper" => a.value.to_uppercase(),
+                        "lower" => a.value.to_lowercase(),
+                        "trim" => a.value.trim().to_string(),
+                        _ => a.value.chars().rev().collect(),
+                    };
+                    self.execution_stack.push(Token {
                         proxy: None,
+                        token_type: TokenTypes::String,
+                        value,
+                        block: vec![],
+                        line_number: 0,
+                        row: 0,
                     })
                 } else {
                     print_error(
@@ -1850,45 +4345,24 @@ impl ManitcoreVm {
                         &self.file,
                         &self.last_instruction,
                     )
-                };
+                }
             }
-            "/" => {
-                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+            "starts_with" | "ends_with" => {
+                if let (Some(part), Some(text)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: f32 = 0.0;
-                    let mut s: f32 = 0.0;
-
-                    if let Ok(v) = a.value.parse() {
-                        f = v
+                    let found = if i.value == "starts_with" {
+                        text.value.starts_with(&part.value)
                     } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
-
-                    if let Ok(v) = b.value.parse() {
-                        s = v
-                    } else {
-                        print_error(
-                            "expected a number",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
-
+                        text.value.ends_with(&part.value)
+                    };
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Number,
-                        value: (s / f).to_string(),
+                        proxy: None,
+                        token_type: TokenTypes::Bool,
+                        value: found.to_string(),
+                        block: vec![],
                         line_number: 0,
                         row: 0,
-                        block: vec![],
-                        proxy: None,
                     })
                 } else {
                     print_error(
@@ -1898,45 +4372,31 @@ impl ManitcoreVm {
                         &self.file,
                         &self.last_instruction,
                     )
-                };
+                }
             }
-            "concat" => {
-                if let (Some(a), Some(b)) = (self.execution_stack.pop(), self.execution_stack.pop())
+            "repeat" => {
+                if let (Some(count), Some(text)) =
+                    (self.execution_stack.pop(), self.execution_stack.pop())
                 {
-                    let mut f: String = String::new();
-                    let mut s: String = String::new();
-
-                    if let Ok(v) = a.value.parse() {
-                        f = v
-                    } else {
-                        print_error(
-                            "expected a string",
-                            i.line_number,
-                            i.row,
-                            &self.file,
-                            &self.last_instruction,
-                        )
-                    }
-
-                    if let Ok(v) = b.value.parse() {
-                        s = v
+                    let mut n: usize = 0;
+                    if let Ok(v) = count.value.parse() {
+                        n = v
                     } else {
                         print_error(
-                            "expected a string",
-                            i.line_number,
-                            i.row,
+                            "expected a positive whole number",
+                            count.line_number,
+                            count.row,
                             &self.file,
                             &self.last_instruction,
                         )
                     }
-
                     self.execution_stack.push(Token {
-                        token_type: TokenTypes::Number,
-                        value: s + &f,
+                        proxy: None,
+                        token_type: TokenTypes::String,
+                        value: text.value.repeat(n),
+                        block: vec![],
                         line_number: 0,
                         row: 0,
-                        block: vec![],
-                        proxy: None,
                     })
                 } else {
                     print_error(
@@ -1946,7 +4406,7 @@ impl ManitcoreVm {
                         &self.file,
                         &self.last_instruction,
                     )
-                };
+                }
             }
             "dup" => {
                 if let Some(a) = self.execution_stack.pop() {
@@ -1962,55 +4422,17 @@ impl ManitcoreVm {
                     )
                 };
             }
+            // Escapes were already decoded by the lexer
             "println" => {
-                let mut escape_char = false;
                 if let Some(a) = self.execution_stack.pop() {
-                    for c in a.value.chars() {
-                        if !escape_char && c == '\\' {
-                            escape_char = true;
-                            continue;
-                        }
-                        if escape_char {
-                            if c == 'n' {
-                                println!();
-                                escape_char = false;
-                                continue;
-                            }
-                            if c == 't' {
-                                print!("\t");
-                                escape_char = false;
-                                continue;
-                            }
-                        }
-                        print!("{}", c)
-                    }
-                    println!();
+                    println!("{}", a.value);
                 } else {
                     println!();
                 };
             }
             "print" => {
-                let mut escape_char = false;
                 if let Some(a) = self.execution_stack.pop() {
-                    for c in a.value.chars() {
-                        if !escape_char && c == '\\' {
-                            escape_char = true;
-                            continue;
-                        }
-                        if escape_char {
-                            if c == 'n' {
-                                println!();
-                                escape_char = false;
-                                continue;
-                            }
-                            if c == 't' {
-                                print!("\t");
-                                escape_char = false;
-                                continue;
-                            }
-                        }
-                        print!("{}", c)
-                    }
+                    print!("{}", a.value);
                 } else {
                     println!()
                 };
@@ -2027,3 +4449,284 @@ impl ManitcoreVm {
         }
     }
 }
+
+// Turns an index that may count back from the end into a position
+fn resolve_index(index: i64, length: usize) -> Option<usize> {
+    let position = if index < 0 {
+        index + length as i64
+    } else {
+        index
+    };
+    if position >= 0 && position < length as i64 {
+        Some(position as usize)
+    } else {
+        None
+    }
+}
+
+// The positions a slice visits, bounds past either end are clamped the
+// same way python does it
+fn slice_indexes(length: usize, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let length = length as i64;
+    let clamp = |bound: i64, low: i64, high: i64| {
+        let bound = if bound < 0 { bound + length } else { bound };
+        bound.clamp(low, high)
+    };
+    let mut indexes = vec![];
+    if step > 0 {
+        let mut n = start.map_or(0, |s| clamp(s, 0, length));
+        let end = end.map_or(length, |e| clamp(e, 0, length));
+        while n < end {
+            indexes.push(n as usize);
+            n += step;
+        }
+    } else {
+        let mut n = start.map_or(length - 1, |s| clamp(s, -1, length - 1));
+        let end = end.map_or(-1, |e| clamp(e, -1, length - 1));
+        while n > end {
+            indexes.push(n as usize);
+            n += step;
+        }
+    }
+    indexes
+}
+
+// A stable merge sort, comparators written in core code can be
+// inconsistent so the standard library sort is not used
+fn merge_sort(
+    mut items: Vec<Token>,
+    compare: &mut dyn FnMut(&Token, &Token) -> std::cmp::Ordering,
+) -> Vec<Token> {
+    if items.len() < 2 {
+        return items;
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, compare);
+    let right = merge_sort(right, compare);
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Ties keep the item from the left half first
+        if compare(b, a) == std::cmp::Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+// The statement var name: value; as tokens
+fn define(name: &str, value: Token) -> [Token; 5] {
+    let symbol = |value: &str| Token {
+        proxy: None,
+        token_type: TokenTypes::Symbol,
+        value: value.to_string(),
+        block: vec![],
+        line_number: 0,
+        row: 0,
+    };
+    [
+        Token {
+            proxy: None,
+            token_type: TokenTypes::Function,
+            value: "var".to_string(),
+            block: vec![],
+            line_number: 0,
+            row: 0,
+        },
+        Token {
+            proxy: None,
+            token_type: TokenTypes::Identifier,
+            value: name.to_string(),
+            block: vec![],
+            line_number: 0,
+            row: 0,
+        },
+        symbol(":"),
+        value,
+        symbol(";"),
+    ]
+}
+
+// Blocks stored with = start with var name: block; so they can use
+// their own name, this gives back the block that was stored so
+// defining it again does not wrap it twice
+fn without_prefix(value: Token) -> Token {
+    if value.token_type != TokenTypes::Block {
+        return value;
+    }
+    match (&value.proxy, value.block.get(..5)) {
+        (Some(name), Some([var, id, _, inner, _]))
+            if var.value == "var"
+                && id.value == *name
+                && inner.token_type == TokenTypes::Block =>
+        {
+            inner.clone()
+        }
+        _ => value,
+    }
+}
+
+// The items as a set. Sets keep their items in set_cmp order so they
+// can be searched, only the first of equal items is kept
+fn set_of(mut items: Vec<Token>, line_number: usize, row: usize) -> Token {
+    items.sort_by(|a, b| a.set_cmp(b));
+    items.dedup_by(|a, b| a.set_cmp(b) == std::cmp::Ordering::Equal);
+    Token {
+        proxy: None,
+        token_type: TokenTypes::Set,
+        value: "set".to_string(),
+        block: items,
+        line_number,
+        row,
+    }
+}
+
+// Where the items of a list or deque start, past a deque's free slots
+fn deque_start(list: &Token) -> usize {
+    if list.token_type == TokenTypes::Deque {
+        list.block.len() - list.deque_items().len()
+    } else {
+        0
+    }
+}
+
+// Puts an item in front of a deque in a free slot, making as many free
+// slots as it holds when there are none left
+fn deque_push_front(deque: &mut Token, item: Token) {
+    let mut head = deque.block[0].value.parse::<usize>().unwrap_or(0);
+    if head == 0 {
+        head = deque.deque_items().len().max(1);
+        let free = vec![
+            Token {
+                proxy: None,
+                token_type: TokenTypes::Nothing,
+                value: "_".to_string(),
+                block: vec![],
+                line_number: 0,
+                row: 0,
+            };
+            head
+        ];
+        deque.block.splice(1..1, free);
+    }
+    deque.block[head] = item;
+    deque.block[0].value = (head - 1).to_string();
+}
+
+// Takes the first item of a deque and leaves a free slot behind it.
+// The free slots are dropped once they outnumber the items
+fn deque_pop_front(deque: &mut Token) -> Option<Token> {
+    let head = deque.block[0].value.parse::<usize>().unwrap_or(0);
+    if deque.block.len() <= head + 1 {
+        return None;
+    }
+    let item = std::mem::replace(
+        &mut deque.block[head + 1],
+        Token {
+            proxy: None,
+            token_type: TokenTypes::Nothing,
+            value: "_".to_string(),
+            block: vec![],
+            line_number: 0,
+            row: 0,
+        },
+    );
+    let head = head + 1;
+    if head > deque.block.len() - 1 - head {
+        deque.block.drain(1..1 + head);
+        deque.block[0].value = "0".to_string();
+    } else {
+        deque.block[0].value = head.to_string();
+    }
+    Some(item)
+}
+
+// Takes the top item of a queue, the last entry of the heap takes its
+// place and sinks down to where it belongs
+fn queue_pop(queue: &mut Token) -> Option<Token> {
+    if queue.block.len() < 2 {
+        return None;
+    }
+    let last = queue.block.len() - 1;
+    queue.block.swap(1, last);
+    let mut entry = queue.block.pop()?;
+    let len = queue.block.len();
+    let mut n = 1;
+    loop {
+        let mut first = n;
+        for child in [2 * n, 2 * n + 1] {
+            if child < len
+                && queue.queue_cmp(&queue.block[child], &queue.block[first]) == std::cmp::Ordering::Less
+            {
+                first = child;
+            }
+        }
+        if first == n {
+            break;
+        }
+        queue.block.swap(n, first);
+        n = first;
+    }
+    Some(entry.block.remove(2))
+}
+
+fn map_find(map: &Token, key: &Token) -> Result<usize, usize> {
+    map.block.binary_search_by(|pair| pair.block[0].set_cmp(key))
+}
+
+// Sets the value of a key in a sorted map, keeping the keys in order
+fn map_put(map: &mut Token, key: Token, value: Token) {
+    match map_find(map, &key) {
+        Ok(n) => map.block[n].block[1] = value,
+        Err(n) => map.block.insert(
+            n,
+            Token {
+                proxy: None,
+                token_type: TokenTypes::List,
+                value: "list".to_string(),
+                block: vec![key, value],
+                line_number: 0,
+                row: 0,
+            },
+        ),
+    }
+}
+
+// How many numbers a range hands out, its end is included
+fn range_len(range: &Token) -> usize {
+    let bound = |n: usize| range.block[n].value.parse::<f64>().unwrap_or(0.0);
+    let (start, end, step) = (bound(0), bound(1), bound(2));
+    let span = (end - start) / step;
+    if span < 0.0 || !span.is_finite() {
+        return 0;
+    }
+    // A little slack so float steps still land on the end
+    (span + 1e-9).floor() as usize + 1
+}
+
+// The nth number of a range, whole number ranges stay exact past
+// what an f32 can hold
+fn range_value(range: &Token, n: usize) -> Token {
+    let whole = |t: &Token| t.value.parse::<i64>().ok();
+    let value = match (whole(&range.block[0]), whole(&range.block[2])) {
+        (Some(start), Some(step)) => (start + step * n as i64).to_string(),
+        _ => {
+            let bound = |n: usize| range.block[n].value.parse::<f64>().unwrap_or(0.0);
+            ((bound(0) + bound(2) * n as f64) as f32).to_string()
+        }
+    };
+    Token {
+        proxy: None,
+        token_type: TokenTypes::Number,
+        value,
+        block: vec![],
+        line_number: range.line_number,
+        row: range.row,
+    }
+}
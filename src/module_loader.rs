@@ -0,0 +1,180 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{ast, lexer::Lexer, token::Token};
+
+// Shared between a vm and every vm it spawns, so a module only
+// ever executes once no matter where the import happens
+pub type SharedModules = Rc<RefCell<ModuleLoader>>;
+
+// Standard library modules bundled into the interpreter
+const STD_MODULES: [(&str, &str); 4] = [
+    ("list", include_str!("../std/list.core")),
+    ("math", include_str!("../std/math.core")),
+    ("string", include_str!("../std/string.core")),
+    ("io", include_str!("../std/io.core")),
+];
+
+// Finds a bundled module by the path resolve gave it
+fn std_source(path: &Path) -> Option<&'static str> {
+    STD_MODULES
+        .iter()
+        .find(|(name, _)| path == Path::new("std").join(format!("{}.core", name)))
+        .map(|(_, source)| *source)
+}
+
+// What an importer gets to see of a module
+#[derive(Clone)]
+pub struct Module {
+    pub public: HashMap<String, Token>,
+    pub private: Vec<String>,
+}
+
+impl Module {
+    // Modules that call export only share what they list, otherwise
+    // everything except names starting with an underscore is shared
+    pub fn new(heap: HashMap<String, Token>, exports: &Option<Vec<String>>) -> Self {
+        let mut public = HashMap::new();
+        let mut private = vec![];
+        for (name, tok) in heap {
+            let shared = match exports {
+                Some(list) => list.contains(&name),
+                None => !name.starts_with('_'),
+            };
+            if shared {
+                public.insert(name, tok);
+            } else {
+                private.push(name);
+            }
+        }
+        Module { public, private }
+    }
+}
+
+pub struct ModuleLoader {
+    // Files currently being imported, outermost first
+    loading: Vec<PathBuf>,
+    // What each module defined, keyed by canonical path
+    loaded: HashMap<PathBuf, Module>,
+    // Lexed source of each module, keyed by canonical path
+    sources: HashMap<PathBuf, Vec<Token>>,
+    // Definitions from the standard prelude, visible from every scope
+    prelude: HashMap<String, Token>,
+    // Extra directories to look for modules in
+    search_path: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        ModuleLoader {
+            loading: Vec::new(),
+            loaded: HashMap::new(),
+            sources: HashMap::new(),
+            prelude: HashMap::new(),
+            search_path: Vec::new(),
+        }
+    }
+
+    pub fn set_prelude(&mut self, definitions: HashMap<String, Token>) {
+        self.prelude = definitions
+    }
+
+    pub fn set_search_path(&mut self, dirs: Vec<PathBuf>) {
+        self.search_path = dirs
+    }
+
+    // Looks up a name the prelude defined
+    pub fn prelude(&self, name: &str) -> Option<Token> {
+        self.prelude.get(name).cloned()
+    }
+
+    // Marks the script being run so imports resolve next to it
+    // and importing it again counts as a cycle
+    pub fn set_main(&mut self, file: &str) {
+        if let Ok(path) = Path::new(file).canonicalize() {
+            self.loading.clear();
+            self.loading.push(path)
+        }
+    }
+
+    // Looks for a module next to the importing file, then in each
+    // directory of the search path, then in the standard library and
+    // finally in the working directory. The .core extension may be left off
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let mut dirs = vec![];
+        if let Some(dir) = self.loading.last().and_then(|p| p.parent()) {
+            dirs.push(dir.to_path_buf())
+        }
+        dirs.extend(self.search_path.iter().cloned());
+
+        let find = |dir: &Path| {
+            [dir.join(name), dir.join(format!("{}.core", name))]
+                .into_iter()
+                .find(|candidate| candidate.is_file())
+                .and_then(|candidate| candidate.canonicalize().ok())
+        };
+        if let Some(path) = dirs.iter().find_map(|dir| find(dir)) {
+            return Some(path);
+        }
+
+        // Bundled modules are keyed by a relative path so they can
+        // never clash with a file on disk
+        let std_name = name.trim_start_matches("std/").trim_end_matches(".core");
+        let std_path = Path::new("std").join(format!("{}.core", std_name));
+        if std_source(&std_path).is_some() {
+            return Some(std_path);
+        }
+
+        find(Path::new("."))
+    }
+
+    // Returns the lexed tokens of a module, reading the file only once.
+    // A module that does not lex or parse stops the program
+    pub fn source(&mut self, path: &Path) -> Vec<Token> {
+        if let Some(tokens) = self.sources.get(path) {
+            return tokens.clone();
+        }
+        let mut lexer = if let Some(source) = std_source(path) {
+            Lexer::new_from_string(source)
+        } else {
+            Lexer::new_from_file(&path.to_string_lossy())
+        };
+        lexer.parse();
+        if lexer.report_errors() {
+            std::process::exit(1)
+        }
+        let tokens = lexer.block_stack[0].clone();
+        ast::parse_file(&tokens, &path.to_string_lossy());
+        self.sources.insert(path.to_path_buf(), tokens.clone());
+        tokens
+    }
+
+    // What a module defined the first time it was imported
+    pub fn cached(&self, path: &Path) -> Option<Module> {
+        self.loaded.get(path).cloned()
+    }
+
+    // Starts importing a module, failing if it is already being imported
+    pub fn enter(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|p| p == path) {
+            let mut cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            cycle.push(path.display().to_string());
+            return Err(format!("circular import: {}", cycle.join(" -> ")));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    // Finishes importing a module and caches what it defined
+    pub fn leave(&mut self, path: &Path, module: Module) {
+        self.loading.pop();
+        self.loaded.insert(path.to_path_buf(), module);
+    }
+}
@@ -1,12 +1,13 @@
 use colored::Colorize;
 
 use crate::{
+    ast::{self, Expr, Node, Span, Statement},
     string_utils::is_string_number,
     token::{Token, TokenTypes},
 };
 
+// Turns the syntax tree into the postfix tokens the vm runs
 pub struct Parser {
-    pub operator_stack: Vec<Token>,
     pub output_stack: Vec<Token>,
     pub debug: bool,
 }
@@ -14,514 +15,362 @@ pub struct Parser {
 impl Parser {
     pub fn new() -> Self {
         Parser {
-            operator_stack: Vec::new(),
             output_stack: Vec::new(),
             debug: false,
         }
     }
 
     pub fn _clear(&mut self) {
-        self.operator_stack.clear();
         self.output_stack.clear();
     }
 
-    pub fn shunt(&mut self, input: &[Token]) -> &Vec<Token> {
-        for token in input {
-            // (Numbers, Strings, Bool) gets passed to output stack
-            if token.token_type == TokenTypes::Number {
-                self.output_stack.push(token.clone());
+    // Pushes an identifier, splitting hello.world.-1 into
+    // hello world . -1 . for the vm to walk
+    fn push_identifier(&mut self, ident: &Token) {
+        if !ident.value.contains('.') {
+            self.output_stack.push(ident.clone());
+            return;
+        }
+        let mut parts = ident.value.split('.');
+        if let Some(first) = parts.next() {
+            if !first.is_empty() {
+                self.output_stack.push(Token {
+                    token_type: TokenTypes::Identifier,
+                    value: first.to_string(),
+                    line_number: ident.line_number,
+                    row: ident.row,
+                    block: vec![],
+                    proxy: None,
+                });
             }
+        }
+        for part in parts.filter(|p| !p.is_empty()) {
+            let token_type = if is_string_number(part.strip_prefix('-').unwrap_or(part)) {
+                TokenTypes::Number
+            } else {
+                TokenTypes::Identifier
+            };
+            self.output_stack.push(Token {
+                token_type,
+                value: part.to_string(),
+                line_number: ident.line_number,
+                row: ident.row,
+                block: vec![],
+                proxy: None,
+            });
+            self.output_stack.push(Token {
+                token_type: TokenTypes::Symbol,
+                value: ".".to_string(),
+                line_number: ident.line_number,
+                row: ident.row,
+                block: vec![],
+                proxy: None,
+            })
+        }
+    }
 
-            if token.token_type == TokenTypes::String {
-                self.output_stack.push(token.clone());
+    // Lowers [x * x for x: xs if gtr(x 0)] into the names, the shunted
+    // collection, a block for the item and one for the filter, ready for
+    // the vm to loop over the same way for does. Lists that are not
+    // comprehensions are left alone
+    fn comprehension(&mut self, list: &Token) -> bool {
+        let is_keyword = |t: &Token, name: &str| t.token_type == TokenTypes::Function && t.value == name;
+        let tokens = &list.block;
+        let start = match tokens.iter().position(|t| is_keyword(t, "for")) {
+            Some(n) if n > 0 => n,
+            _ => return false,
+        };
+
+        // for x: or for [i x], a single name is bound rather than read
+        let (names, rest) = match &tokens[start + 1..] {
+            [name, colon, rest @ ..]
+                if name.token_type == TokenTypes::Identifier && colon.value == ":" =>
+            {
+                let mut name = name.clone();
+                name.proxy = Some(name.value.clone());
+                (name, rest)
             }
-            if token.token_type == TokenTypes::Bool {
-                self.output_stack.push(token.clone());
+            [pattern, rest @ ..] if pattern.token_type == TokenTypes::List => {
+                (pattern.clone(), rest)
             }
+            _ => return false,
+        };
+        let (collection, filter) = match rest.iter().position(|t| is_keyword(t, "if")) {
+            Some(n) => (&rest[..n], Some(&rest[n + 1..])),
+            None => (rest, None),
+        };
+        if collection.is_empty() {
+            return false;
+        }
 
-            if token.token_type == TokenTypes::Break {
-                self.output_stack.push(token.clone());
+        let block = |body: &[Token]| {
+            let mut tokens = vec![Token {
+                token_type: TokenTypes::Break,
+                value: "break".to_string(),
+                line_number: list.line_number,
+                row: list.row,
+                block: vec![],
+                proxy: None,
+            }];
+            tokens.extend(read_in_place(body));
+            Token {
+                token_type: TokenTypes::Block,
+                value: "block".to_string(),
+                line_number: list.line_number,
+                row: list.row,
+                block: tokens,
+                proxy: None,
             }
+        };
+
+        self.output_stack.push(names);
+        let mut parser = Parser::new();
+        parser.debug = self.debug;
+        self.output_stack.extend(parser.shunt(collection).clone());
+        self.output_stack.push(block(&tokens[..start]));
+        self.output_stack.push(match filter {
+            Some(filter) => block(filter),
+            None => Token {
+                token_type: TokenTypes::Nothing,
+                value: "_".to_string(),
+                line_number: list.line_number,
+                row: list.row,
+                block: vec![],
+                proxy: None,
+            },
+        });
+        self.output_stack.push(Token {
+            token_type: TokenTypes::Function,
+            value: "comprehension".to_string(),
+            line_number: list.line_number,
+            row: list.row,
+            block: vec![],
+            proxy: None,
+        });
+        true
+    }
 
-            if token.token_type == TokenTypes::Nothing {
-                self.output_stack.push(token.clone());
-            }
-            // List go to output stack
-            if token.token_type == TokenTypes::List {
-                self.output_stack.push(token.clone());
-            }
+    // Parses the tokens and lowers them, code that does not parse stops
+    // the program
+    pub fn shunt(&mut self, input: &[Token]) -> &Vec<Token> {
+        let statements = ast::parse_file(input, "");
+        self.lower(&statements)
+    }
 
-            // Blocked gets passed to output stack
-            // Will bring @ function if its on the operator stack
-            if token.token_type == TokenTypes::Block {
-                self.output_stack.push(token.clone());
-                if let Some(last) = self.operator_stack.last().cloned() {
-                    if last.value == "@" {
-                        self.operator_stack.pop();
-                        self.output_stack.push(last)
-                    }
-                }
-            }
+    pub fn lower(&mut self, statements: &[Statement]) -> &Vec<Token> {
+        for statement in statements {
+            self.lower_node(statement.node.clone());
+        }
 
-            // identifiers get passed to operator stack
-            if token.token_type == TokenTypes::Identifier {
-                self.operator_stack.push(token.clone())
+        if self.debug {
+            let mut printstack: String = "".to_string();
+            for t in &self.output_stack {
+                let ty = format!("{:?}", &t.token_type);
+                printstack.push_str(&("[".to_owned() + &t.value + " -> " + ty.as_str() + "]"));
+                //printstack.push_str(&("[".to_owned() + &t.value + "]"));
+                printstack.push(' ');
             }
+            println!("STACK: {}", &printstack.bright_green());
+        }
+        &self.output_stack
+    }
 
-            // Functions get passed to operator stack
-            if token.token_type == TokenTypes::Function {
-                self.operator_stack.push(token.clone())
+    // Operands go out before what works on them
+    fn lower_node(&mut self, node: Node) {
+        let span = node.span;
+        match node.expr {
+            Expr::Number(value) => {
+                self.output_stack
+                    .push(token_at(TokenTypes::Number, &value, span))
             }
-
-            if token.token_type == TokenTypes::Symbol {
-                match token.value.as_str() {
-                    "," => {
-                        // pop temp off if its and check if its "("
-                        if let Some(temp) = self.operator_stack.pop() {
-                            if temp.value == "(" {
-                                if let Some(func) = self.operator_stack.last().cloned() {
-                                    // if it is function pop function
-                                    if func.token_type == TokenTypes::Function {
-                                        self.output_stack.push(func.clone())
-                                    }
-
-                                    // if it is identifier then pop and add @
-                                    if func.token_type == TokenTypes::Identifier {
-                                        // Check for complex identifier
-                                        if func.value.contains('.') {
-                                            let mut buffer = String::new();
-                                            let mut list = Vec::new();
-
-                                            // Split by dots
-                                            // hello.world
-                                            for c in func.value.chars() {
-                                                if c == '.' {
-                                                    list.push(buffer.clone());
-                                                    buffer.clear()
-                                                } else {
-                                                    buffer += &c.to_string();
-                                                }
-                                            }
-                                            if !buffer.is_empty() {
-                                                list.push(buffer.clone())
-                                            }
-                                            buffer.clear();
-
-                                            // [hello] [world]
-                                            list.reverse();
-
-                                            // [world] [hello]
-                                            // Push first item
-                                            if let Some(t) = list.pop() {
-                                                if !t.is_empty() {
-                                                    self.output_stack.push(Token {
-                                                        token_type: TokenTypes::Identifier,
-                                                        value: t.to_string(),
-                                                        line_number: func.line_number,
-                                                        row: func.row,
-                                                        block: vec![],
-                                                        proxy: None,
-                                                    });
-                                                }
-                                            }
-
-                                            list.reverse();
-                                            // [world]
-                                            for t in list {
-                                                if is_string_number(&t) {
-                                                    self.output_stack.push(Token {
-                                                        token_type: TokenTypes::Number,
-                                                        value: t.to_string(),
-                                                        line_number: func.line_number,
-                                                        row: func.row,
-                                                        block: vec![],
-                                                        proxy: None,
-                                                    });
-                                                } else {
-                                                    self.output_stack.push(Token {
-                                                        token_type: TokenTypes::Identifier,
-                                                        value: t.to_string(),
-                                                        line_number: func.line_number,
-                                                        row: func.row,
-                                                        block: vec![],
-                                                        proxy: None,
-                                                    });
-                                                }
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Symbol,
-                                                    value: ".".to_string(),
-                                                    line_number: func.line_number,
-                                                    row: func.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                })
-                                            }
-                                            self.output_stack.push(Token {
-                                                token_type: TokenTypes::Symbol,
-                                                value: "@".to_string(),
-                                                line_number: func.line_number,
-                                                row: func.row,
-                                                block: vec![],
-                                                proxy: None,
-                                            })
-                                        } else {
-                                            self.output_stack.push(func);
-                                            self.output_stack.push(Token {
-                                                token_type: TokenTypes::Symbol,
-                                                value: "@".to_string(),
-                                                line_number: token.line_number,
-                                                row: token.row,
-                                                block: vec![],
-                                                proxy: None,
-                                            })
-                                        }
-                                    }
-                                }
-                            }
-                            // put temp back
-                            self.operator_stack.push(temp)
-                        }
-                    }
-                    "(" => {
-                        self.operator_stack.push(token.clone());
-                    }
-                    ")" => {
-                        if let Some(last) = self.operator_stack.last() {
-                            if last.value == "(" {}
-                        }
-                        // while the last item in the operator stack is not
-                        // a "(", pop off items into output stack
-                        while let Some(last) = self.operator_stack.pop() {
-                            if last.value != "(" {
-                                if last.token_type == TokenTypes::Identifier {
-                                    // Check for complex identifier
-                                    if last.value.contains('.') {
-                                        let mut buffer = String::new();
-                                        let mut list = Vec::new();
-
-                                        // Split by dots
-                                        // hello.world
-                                        for c in last.value.chars() {
-                                            if c == '.' {
-                                                list.push(buffer.clone());
-                                                buffer.clear()
-                                            } else {
-                                                buffer += &c.to_string();
-                                            }
-                                        }
-                                        if !buffer.is_empty() {
-                                            list.push(buffer.clone())
-                                        }
-                                        buffer.clear();
-
-                                        // [hello] [world]
-                                        list.reverse();
-
-                                        // [world] [hello]
-                                        // Push first item
-                                        if let Some(t) = list.pop() {
-                                            if !t.is_empty() {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            }
-                                        }
-                                        list.reverse();
-                                        // [world]
-                                        for t in list {
-                                            if is_string_number(&t) {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Number,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            } else {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            }
-                                            self.output_stack.push(Token {
-                                                token_type: TokenTypes::Symbol,
-                                                value: ".".to_string(),
-                                                line_number: last.line_number,
-                                                row: last.row,
-                                                block: vec![],
-                                                proxy: None,
-                                            })
-                                        }
-                                    } else {
-                                        self.output_stack.push(last)
-                                    }
-                                    continue;
-                                }
-                                self.output_stack.push(last)
-                            } else {
-                                break;
-                            }
-                        }
-
-                        // if last item on operator stack is a function pop
-                        // this is for leapfrog TM parsing
-                        if let Some(last) = self.operator_stack.pop() {
-                            match last.token_type {
-                                TokenTypes::Function => {
-                                    self.output_stack.push(last);
-                                }
-                                TokenTypes::Identifier => {
-                                    // Check for complex identifier
-                                    if last.value.contains('.') {
-                                        let mut buffer = String::new();
-                                        let mut list = Vec::new();
-
-                                        // Split by dots
-                                        // hello.world
-                                        for c in last.value.chars() {
-                                            if c == '.' {
-                                                list.push(buffer.clone());
-                                                buffer.clear()
-                                            } else {
-                                                buffer += &c.to_string();
-                                            }
-                                        }
-                                        if !buffer.is_empty() {
-                                            list.push(buffer.clone())
-                                        }
-                                        buffer.clear();
-
-                                        // [hello] [world]
-                                        list.reverse();
-
-                                        // [world] [hello]
-                                        // Push first item
-                                        if let Some(t) = list.pop() {
-                                            if !t.is_empty() {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            }
-                                        }
-
-                                        list.reverse();
-                                        // [world]
-                                        for t in list {
-                                            if is_string_number(&t) {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Number,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            } else {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            }
-                                            self.output_stack.push(Token {
-                                                token_type: TokenTypes::Symbol,
-                                                value: ".".to_string(),
-                                                line_number: last.line_number,
-                                                row: last.row,
-                                                block: vec![],
-                                                proxy: None,
-                                            })
-                                        }
-                                        self.output_stack.push(Token {
-                                            token_type: TokenTypes::Symbol,
-                                            value: "@".to_string(),
-                                            line_number: last.line_number,
-                                            row: last.row,
-                                            block: vec![],
-                                            proxy: None,
-                                        })
-                                    } else {
-                                        self.output_stack.push(last);
-                                        self.output_stack.push(Token {
-                                            token_type: TokenTypes::Symbol,
-                                            value: "@".to_string(),
-                                            line_number: token.line_number,
-                                            row: token.row,
-                                            block: vec![],
-                                            proxy: None,
-                                        })
-                                    }
-                                }
-                                _ => self.operator_stack.push(last),
-                            }
-                        }
-                    }
-                    "+" | "-" | "*" | "/" => {
-                        //Pop off higher precedence before adding
-
-                        // if last item in operator stack is not a "("
-                        // and while last item precedence is > than
-                        // current token precedence pop until empty
-                        if let Some(temp) = self.operator_stack.last().cloned() {
-                            if temp.value != "(" {
-                                while let Some(op) = self.operator_stack.last().cloned() {
-                                    if op.precedence() > token.precedence() {
-                                        if let Some(t) = self.operator_stack.pop() {
-                                            self.output_stack.push(t)
-                                        }
-                                    } else {
-                                        break;
-                                    }
-                                }
-
-                                // if operator last on the stack is of equal precedence, then pop
-                                // until empty
-                                while let Some(op) = self.operator_stack.last().cloned() {
-                                    if op.precedence() == token.precedence()
-                                        && token.is_left_associative()
-                                    {
-                                        if let Some(t) = self.operator_stack.pop() {
-                                            self.output_stack.push(t)
-                                        }
-                                    } else {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-
-                        // push token onto operator stack
-                        self.operator_stack.push(token.clone());
-                        continue;
+            Expr::Bool(value) => {
+                self.output_stack
+                    .push(token_at(TokenTypes::Bool, &value.to_string(), span))
+            }
+            Expr::Nothing => self
+                .output_stack
+                .push(token_at(TokenTypes::Nothing, "_", span)),
+            Expr::String { token, .. } | Expr::Block { token, .. } | Expr::Value(token) => {
+                self.output_stack.push(token)
+            }
+            Expr::List { token, .. } => {
+                if !self.comprehension(&token) {
+                    self.output_stack.push(token)
+                }
+            }
+            Expr::Name { path, .. } => {
+                self.push_identifier(&token_at(TokenTypes::Identifier, &path.join("."), span))
+            }
+            Expr::Call {
+                name,
+                builtin,
+                args,
+                commas,
+                ..
+            } => {
+                // Every comma calls with the arguments so far. Bare names
+                // wait for the other arguments and go out last first, so
+                // concat(a b) joins b and a
+                let count = args.len();
+                let mut waiting = vec![];
+                for (n, arg) in args.into_iter().enumerate() {
+                    for _ in commas.iter().filter(|c| **c == n && n > 0) {
+                        self.release(&mut waiting);
+                        self.push_call(&name, builtin, span);
                     }
-                    ";" => {
-                        while let Some(tok) = self.operator_stack.pop() {
-                            self.output_stack.push(tok)
-                        }
+                    if builtin && binds(&name, n) {
+                        self.push_binder(arg)
+                    } else if matches!(arg.expr, Expr::Name { now: false, .. }) {
+                        waiting.push(arg)
+                    } else {
+                        self.lower_node(arg)
                     }
-                    ":" => {
-                        if let Some(last) = self.operator_stack.pop() {
-                            match last.token_type {
-                                TokenTypes::Function => {
-                                    self.output_stack.push(last);
-                                }
-                                TokenTypes::Identifier => {
-                                    // Check for complex identifier
-                                    if last.value.contains('.') {
-                                        let mut buffer = String::new();
-                                        let mut list = Vec::new();
-
-                                        // Split by dots
-                                        // hello.world
-                                        for c in last.value.chars() {
-                                            if c == '.' {
-                                                list.push(buffer.clone());
-                                                buffer.clear()
-                                            } else {
-                                                buffer += &c.to_string();
-                                            }
-                                        }
-                                        if !buffer.is_empty() {
-                                            list.push(buffer.clone())
-                                        }
-                                        buffer.clear();
-
-                                        // [hello] [world]
-                                        list.reverse();
-
-                                        // [world] [hello]
-                                        // Push first item
-                                        if let Some(t) = list.pop() {
-                                            if !t.is_empty() {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                })
-                                            }
-                                        }
-                                        // [world]
-                                        list.reverse();
-                                        for t in list {
-                                            if is_string_number(&t) {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Number,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            } else {
-                                                self.output_stack.push(Token {
-                                                    token_type: TokenTypes::Identifier,
-                                                    value: t.to_string(),
-                                                    line_number: last.line_number,
-                                                    row: last.row,
-                                                    block: vec![],
-                                                    proxy: None,
-                                                });
-                                            }
-
-                                            self.output_stack.push(Token {
-                                                token_type: TokenTypes::Symbol,
-                                                value: ".".to_string(),
-                                                line_number: last.line_number,
-                                                row: last.row,
-                                                block: vec![],
-                                                proxy: None,
-                                            })
-                                        }
-                                    } else {
-                                        self.output_stack.push(last);
-                                    }
-                                }
-                                _ => self.output_stack.push(last),
-                            }
+                }
+                for _ in commas.iter().filter(|c| **c == count && count > 0) {
+                    self.release(&mut waiting);
+                    self.push_call(&name, builtin, span);
+                }
+                self.release(&mut waiting);
+                self.push_call(&name, builtin, span)
+            }
+            Expr::Field { target, path, .. } => {
+                self.lower_node(*target);
+                let path = format!(".{}", path.join("."));
+                self.push_identifier(&token_at(TokenTypes::Identifier, &path, span))
+            }
+            // A minus sign becomes neg and a plus sign changes nothing
+            Expr::Unary { op, operand } => {
+                self.lower_node(*operand);
+                match op.as_str() {
+                    "-" => self
+                        .output_stack
+                        .push(token_at(TokenTypes::Symbol, "neg", span)),
+                    "!" => self
+                        .output_stack
+                        .push(token_at(TokenTypes::Symbol, "!", span)),
+                    _ => {}
+                }
+            }
+            // && and || take their right side with them so the vm only
+            // runs it when needed
+            Expr::Binary { op, left, right } => {
+                self.lower_node(*left);
+                let start = self.output_stack.len();
+                self.lower_node(*right);
+                let mut op = token_at(TokenTypes::Symbol, &op, span);
+                if op.value == "&&" || op.value == "||" {
+                    op.block = self.output_stack.split_off(start);
+                }
+                self.output_stack.push(op)
+            }
+            Expr::Assign { target, value } => {
+                match target.expr {
+                    // a.b.0: = x keeps the whole path so the vm can write
+                    // through it, a plain name is bound to itself so the vm
+                    // knows it is being defined rather than read
+                    Expr::Name { path, now: true } => {
+                        let mut name =
+                            token_at(TokenTypes::Identifier, &path.join("."), target.span);
+                        if path.len() == 1 {
+                            name.proxy = Some(name.value.clone());
                         }
+                        self.output_stack.push(name)
                     }
-                    "=" | "@" | ">" | "<" => self.operator_stack.push(token.clone()),
-                    "~" | "?" => self.output_stack.push(token.clone()),
-                    _ => {}
+                    _ => self.lower_node(*target),
+                }
+                self.lower_node(*value);
+                self.output_stack
+                    .push(token_at(TokenTypes::Symbol, "=", span))
+            }
+            Expr::Group(inner) => self.lower_node(*inner),
+            Expr::Run(inner) => {
+                self.lower_node(*inner);
+                self.output_stack
+                    .push(token_at(TokenTypes::Symbol, "@", span))
+            }
+            Expr::Symbol(value) => {
+                if value == "~" || value == "?" {
+                    self.output_stack
+                        .push(token_at(TokenTypes::Symbol, &value, span))
                 }
             }
         }
+    }
 
-        while let Some(t) = self.operator_stack.pop() {
-            self.output_stack.push(t.clone());
+    // Lowers the bare names a call held back, the last one first
+    fn release(&mut self, waiting: &mut Vec<Node>) {
+        while let Some(name) = waiting.pop() {
+            self.lower_node(name)
         }
+    }
 
-        if self.debug {
-            let mut printstack: String = "".to_string();
-            for t in &self.output_stack {
-                let ty = format!("{:?}", &t.token_type);
-                printstack.push_str(&("[".to_owned() + &t.value + " -> " + ty.as_str() + "]"));
-                //printstack.push_str(&("[".to_owned() + &t.value + "]"));
-                printstack.push(' ');
+    // A name about to be bound goes out with itself as its proxy, the
+    // same as the target of =, so the vm defines it rather than reads it
+    fn push_binder(&mut self, node: Node) {
+        match node.expr {
+            Expr::Name { path, .. } if path.len() == 1 => {
+                let mut name = token_at(TokenTypes::Identifier, &path[0], node.span);
+                name.proxy = Some(path[0].clone());
+                self.output_stack.push(name)
             }
-            println!("STACK: {}", &printstack.bright_green());
+            _ => self.lower_node(node),
         }
-        &self.output_stack
+    }
+
+    // Builtins are called by name, blocks the script defined with @
+    fn push_call(&mut self, name: &[String], builtin: bool, span: Span) {
+        if builtin {
+            self.output_stack
+                .push(token_at(TokenTypes::Function, &name.join("."), span));
+        } else {
+            self.push_identifier(&token_at(TokenTypes::Identifier, &name.join("."), span));
+            self.output_stack
+                .push(token_at(TokenTypes::Symbol, "@", span))
+        }
+    }
+}
+
+// Marks the bare names of a comprehension's item and filter as read
+// where they are written, as if written x:, so [gtr(x 0) for x: xs]
+// compares x with 0 rather than 0 with x
+fn read_in_place(tokens: &[Token]) -> Vec<Token> {
+    let mut marked = vec![];
+    for (n, token) in tokens.iter().enumerate() {
+        marked.push(token.clone());
+        let waits = tokens.get(n + 1).is_some_and(|t| {
+            t.token_type == TokenTypes::Symbol && (t.value == "(" || t.value == ":")
+        });
+        if token.token_type == TokenTypes::Identifier && !waits {
+            marked.push(Token {
+                token_type: TokenTypes::Symbol,
+                value: ":".to_string(),
+                line_number: token.line_number,
+                row: token.row,
+                block: vec![],
+                proxy: None,
+            })
+        }
+    }
+    marked
+}
+
+// Whether a builtin binds the name given as its nth argument, as the
+// x of for x: xs and pop(xs x) do
+fn binds(name: &[String], n: usize) -> bool {
+    match name {
+        [name] if name == "for" => n == 0,
+        [name] if name == "pop" || name == "pop_front" => n == 1,
+        _ => false,
+    }
+}
+
+fn token_at(token_type: TokenTypes, value: &str, span: Span) -> Token {
+    Token {
+        token_type,
+        value: value.to_string(),
+        line_number: span.line_number,
+        row: span.row,
+        block: vec![],
+        proxy: None,
     }
 }
@@ -32,7 +32,7 @@ pub fn is_string_number(data: &str) -> bool {
             return false;
         }
         //Checks to see if it is a number, and makes sure it skips first period
-        if !c.is_numeric() && c != '.' {
+        if !c.is_ascii_digit() && c != '.' {
             return false;
         }
         //Changes deci to true after finding first period
@@ -43,7 +43,65 @@ pub fn is_string_number(data: &str) -> bool {
     true
 }
 
+// Reads a number literal as written in source, returning the value in
+// the plain decimal form the vm works with. Accepts 0x, 0o and 0b
+// prefixes, exponents, a leading minus and _ between digits
+pub fn parse_number(text: &str) -> Option<String> {
+    let (sign, body) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+
+    let (radix, rest) = match body.get(..2) {
+        Some("0x") | Some("0X") => (16, &body[2..]),
+        Some("0o") | Some("0O") => (8, &body[2..]),
+        Some("0b") | Some("0B") => (2, &body[2..]),
+        _ => (10, body),
+    };
+
+    // Underscores may only sit between two digits
+    let chars: Vec<char> = rest.chars().collect();
+    for (n, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            let before = n.checked_sub(1).and_then(|b| chars.get(b));
+            let after = chars.get(n + 1);
+            let digit = |d: Option<&char>| d.is_some_and(|d| d.is_digit(radix));
+            if !digit(before) || !digit(after) {
+                return None;
+            }
+        }
+    }
+    let digits: String = rest.chars().filter(|c| *c != '_').collect();
+
+    if radix != 10 {
+        if !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        let value = i64::from_str_radix(&digits, radix).ok()?;
+        return Some(format!("{}{}", sign, value));
+    }
+
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if is_string_number(&digits) {
+        return Some(format!("{}{}", sign, digits));
+    }
+    // Only exponents are left, they are written out in full
+    if !digits.contains(['e', 'E']) {
+        return None;
+    }
+    let value: f64 = digits.parse().ok()?;
+    Some(format!("{}{}", sign, value))
+}
+
 pub fn print_error(er: &str, line: usize, r: usize, file: &str, last: &str) {
+    show_error(er, line, r, file, last);
+    exit(1)
+}
+
+// Prints an error the same way as print_error without stopping the program
+pub fn show_error(er: &str, line: usize, r: usize, file: &str, last: &str) {
     println!(
         "{}: on line {}, {}",
         "ERROR".red(),
@@ -58,10 +116,15 @@ pub fn print_error(er: &str, line: usize, r: usize, file: &str, last: &str) {
             if linenumber == line {
                 if let Ok(ip) = l {
                     println!("  {}  ", ip.bright_blue());
-                    for _n in 0..r {
-                        print!(" ");
-                    }
-                    println!("{}", "  ^".bright_yellow())
+                    // Columns count characters, tabs are copied so the
+                    // caret lands under the same character
+                    let padding: String = ip
+                        .chars()
+                        .chain(std::iter::repeat(' '))
+                        .take(r)
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+                    println!("{}{}", padding, "  ^".bright_yellow())
                 }
             }
         }
@@ -79,5 +142,4 @@ pub fn print_error(er: &str, line: usize, r: usize, file: &str, last: &str) {
             last.yellow().underline()
         )
     }
-    exit(1)
 }
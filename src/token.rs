@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 #[derive(Debug, Clone, Copy, std::cmp::PartialEq)]
 pub enum TokenTypes {
     Block,
@@ -10,9 +12,22 @@ pub enum TokenTypes {
     Break,
     List,
     Nothing,
+    // Lazy values that hand out items on demand
+    Range,
+    Generator,
+    // Values with no repeats, kept in set_cmp order
+    Set,
+    // A list that is pushed and popped at both ends, the first block
+    // item counts the free slots in front of the items
+    Deque,
+    // A heap of [priority order item] entries after a [key count]
+    // header, the value says min_queue or max_queue
+    Queue,
+    // [key value] pairs kept in key order
+    SortedMap,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     // Used for actual work
     pub proxy: Option<String>,
@@ -26,19 +41,176 @@ pub struct Token {
 }
 
 impl Token {
+    // How a value reads when it is put inside a string
+    pub fn display(&self) -> String {
+        match self.token_type {
+            TokenTypes::List => {
+                let items: Vec<String> = self.block.iter().map(|t| t.display()).collect();
+                format!("[{}]", items.join(" "))
+            }
+            TokenTypes::Range => {
+                let bounds: Vec<&str> = self.block.iter().map(|t| t.value.as_str()).collect();
+                format!("range({})", bounds.join(" "))
+            }
+            TokenTypes::Set => {
+                let items: Vec<String> = self.block.iter().map(|t| t.display()).collect();
+                format!("set_of([{}])", items.join(" "))
+            }
+            TokenTypes::Deque | TokenTypes::Queue | TokenTypes::SortedMap => {
+                let items: Vec<String> = match self.token_type {
+                    TokenTypes::Deque => self.deque_items().iter().map(|t| t.display()).collect(),
+                    TokenTypes::Queue => self.queue_items().iter().map(|t| t.display()).collect(),
+                    _ => self.block.iter().map(|t| t.display()).collect(),
+                };
+                format!("{}([{}])", self.value, items.join(" "))
+            }
+            _ => self.value.clone(),
+        }
+    }
+
+    // Numbers are equal by value, anything else has to be the same
+    // kind of token and read the same
+    pub fn equals(&self, other: &Token) -> bool {
+        if matches!(
+            self.token_type,
+            TokenTypes::Number | TokenTypes::List | TokenTypes::Set
+        ) {
+            return self.hash_key() == other.hash_key();
+        }
+        self.token_type == other.token_type && self.display() == other.display()
+    }
+
+    // A key that two values share exactly when they are equal, so sets
+    // can find them. Strings carry their length so nested ones can not
+    // run together and sets list their keys in order
+    pub fn hash_key(&self) -> String {
+        match self.token_type {
+            TokenTypes::Number => match (whole(&self.value), self.value.parse::<f64>()) {
+                (Some(n), _) => format!("n{}", n),
+                (None, Ok(v)) => format!("n{}", v + 0.0),
+                _ => format!("n{}", self.value),
+            },
+            TokenTypes::String => format!("s{}:{}", self.value.len(), self.value),
+            TokenTypes::List => {
+                let keys: Vec<String> = self.block.iter().map(|t| t.hash_key()).collect();
+                format!("l[{}]", keys.join(","))
+            }
+            TokenTypes::Set => {
+                let mut keys: Vec<String> = self.block.iter().map(|t| t.hash_key()).collect();
+                keys.sort();
+                format!("h[{}]", keys.join(","))
+            }
+            _ => format!("{:?}:{}", self.token_type, self.display()),
+        }
+    }
+
+    // Orders two numbers or two strings, other pairs have no order
+    pub fn compare(&self, other: &Token) -> Option<Ordering> {
+        match (self.token_type, other.token_type) {
+            (TokenTypes::Number, TokenTypes::Number) => {
+                let a = self.value.parse::<f32>().ok()?;
+                let b = other.value.parse::<f32>().ok()?;
+                a.partial_cmp(&b)
+            }
+            (TokenTypes::String, TokenTypes::String) => Some(self.value.cmp(&other.value)),
+            _ => None,
+        }
+    }
+
+    // Orders any two values so mixed lists can be sorted. Nothing comes
+    // first, then bools, numbers, strings, lists and everything else
+    pub fn total_cmp(&self, other: &Token) -> Ordering {
+        let rank = |t: &Token| match t.token_type {
+            TokenTypes::Nothing => 0,
+            TokenTypes::Bool => 1,
+            TokenTypes::Number => 2,
+            TokenTypes::String => 3,
+            TokenTypes::List => 4,
+            TokenTypes::Set => 5,
+            _ => 6,
+        };
+        match (self.token_type, other.token_type) {
+            (TokenTypes::Number, TokenTypes::Number) => {
+                if let (Some(a), Some(b)) = (whole(&self.value), whole(&other.value)) {
+                    return a.cmp(&b);
+                }
+                let a = self.value.parse::<f64>().unwrap_or(f64::NAN);
+                let b = other.value.parse::<f64>().unwrap_or(f64::NAN);
+                a.partial_cmp(&b).unwrap_or_else(|| a.total_cmp(&b))
+            }
+            (TokenTypes::List, TokenTypes::List) => {
+                for (a, b) in self.block.iter().zip(&other.block) {
+                    let order = a.total_cmp(b);
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+                self.block.len().cmp(&other.block.len())
+            }
+            _ => rank(self)
+                .cmp(&rank(other))
+                .then_with(|| self.display().cmp(&other.display())),
+        }
+    }
+
+    // The order sets keep their items in. Two items come out Equal
+    // exactly when they have the same hash key
+    pub fn set_cmp(&self, other: &Token) -> Ordering {
+        self.total_cmp(other)
+            .then_with(|| self.hash_key().cmp(&other.hash_key()))
+    }
+
+    // The items of a deque, past the free slots at the front
+    pub fn deque_items(&self) -> &[Token] {
+        let head = self.block[0].value.parse::<usize>().unwrap_or(0);
+        &self.block[1 + head..]
+    }
+
+    // The items of a priority queue in the order they come out
+    pub fn queue_items(&self) -> Vec<Token> {
+        let mut entries = self.block[1..].to_vec();
+        entries.sort_by(|a, b| self.queue_cmp(a, b));
+        entries.into_iter().map(|mut entry| entry.block.remove(2)).collect()
+    }
+
+    // Which of two entries of a priority queue comes out first, ties
+    // leave in the order they came
+    pub fn queue_cmp(&self, a: &Token, b: &Token) -> Ordering {
+        let priority = a.block[0].total_cmp(&b.block[0]);
+        let priority = if self.value == "max_queue" {
+            priority.reverse()
+        } else {
+            priority
+        };
+        priority.then_with(|| a.block[1].total_cmp(&b.block[1]))
+    }
+
     pub fn precedence(&self) -> usize {
-        match self.value.chars().next() {
-            Some('+') | Some('-') => 4,
-            Some('*') | Some('/') => 5,
+        match self.value.as_str() {
+            "||" => 1,
+            "&&" => 2,
+            "==" | "!=" => 3,
+            "<" | ">" | "<=" | ">=" => 4,
+            "+" | "-" => 5,
+            "*" | "/" | "%" | "//" => 6,
+            "^" | "neg" => 7,
+            "!" => 8,
             _ => 0,
         }
     }
 
+    // 2 ^ 3 ^ 2 is 2 ^ (3 ^ 2) and !!x is !(!x), everything else
+    // groups from the left
     pub fn is_left_associative(&self) -> bool {
-        match self.value.chars().next() {
-            Some('+') | Some('-') => true,
-            Some('*') | Some('/') => true,
-            _ => true,
-        }
+        !matches!(self.value.as_str(), "^" | "!" | "neg")
     }
 }
+
+// A number as an exact whole number when it is one, so large integers
+// past what an f32 can hold stay apart
+fn whole(text: &str) -> Option<i64> {
+    text.parse::<i64>().ok().or_else(|| {
+        let v = text.parse::<f64>().ok()?;
+        (v.fract() == 0.0 && v.abs() < 9.0e18).then_some(v as i64)
+    })
+}
@@ -3,15 +3,27 @@
 #[cfg(test)]
 mod tests {
 
+    use std::path::PathBuf;
+
     use crate::lexer;
     use crate::manticorevm;
+    use crate::module_loader::ModuleLoader;
     use crate::parser;
     
     fn test_vm(input: &str, output: &str) {
+        check_vm(manticorevm::ManitcoreVm::new(&[], ""), input, output)
+    }
 
+    // Same as test_vm but with the standard prelude loaded first
+    fn test_std(input: &str, output: &str) {
+        let mut vm = manticorevm::ManitcoreVm::new(&[], "");
+        vm.load_prelude();
+        check_vm(vm, input, output)
+    }
+
+    fn check_vm(mut vm: manticorevm::ManitcoreVm, input: &str, output: &str) {
         let mut lexer = lexer::Lexer::new_from_string(input);
         lexer.parse();
-        let mut vm = manticorevm::ManitcoreVm::new(&[], "");
         let mut parser = parser::Parser::new();
 
 
@@ -31,6 +43,48 @@ mod tests {
 
     }
 
+    // Writes each (name, source) pair into a fresh directory and runs
+    // main.core from there, the same way a script is run from main.rs
+    fn test_files(dir: &str, files: &[(&str, &str)], input: &str, output: &str) {
+        let dir = write_files(dir, files);
+        let main = dir.join("main.core");
+        std::fs::write(&main, input).unwrap();
+        let main = main.to_string_lossy().to_string();
+
+        let mut lexer = lexer::Lexer::new_from_file(&main);
+        lexer.parse();
+        let mut vm = manticorevm::ManitcoreVm::new(&[], &main);
+        vm.modules.borrow_mut().set_main(&main);
+        let mut parser = parser::Parser::new();
+
+        let shunted = parser.shunt(&lexer.block_stack[0]).clone();
+
+        for i in shunted {
+            vm.execute_token(&i);
+            if vm.exit_loop {
+                break;
+            }
+        }
+        if let Some(results) = vm.execution_stack.pop() {
+            assert_eq!(results.value, output);
+        } else {
+            panic!("didnt get last token");
+        }
+    }
+
+    fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("manticore_test_{}", std::process::id()))
+            .join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
     #[test]
     fn math_stuff() {
         test_vm("1 + 2", "3");
@@ -321,6 +375,894 @@ mod tests {
         
     }
 
+    #[test]
+    fn module_stuff() {
+        // imports resolve next to the importing file
+        test_files(
+            "relative",
+            &[
+                ("a.core", r#"import("b") a: = b: + 1;"#),
+                ("b.core", r#"b: = 41;"#),
+            ],
+            r#"import("a") a:"#,
+            "42",
+        );
+
+        // a module only runs once, later imports reuse its values
+        test_files(
+            "cached",
+            &[("rand.core", r#"r: = randomf(0 1000000);"#)],
+            r#"
+            import("rand")
+            first: = r;
+            import("rand.core")
+            equ(first: r:)
+            "#,
+            "true",
+        );
+
+        // slashes in paths are kept as they are
+        test_files(
+            "nested",
+            &[("lib/a.core", r#"a: = 7;"#)],
+            r#"import("lib/a") a:"#,
+            "7",
+        );
+
+        // the standard library is bundled and importable by name
+        test_files("std", &[], r#"import("math") cube(2)"#, "8");
+        test_files("std_prefix", &[], r#"import("std/list") last([1 2 3])"#, "3");
+
+        // but a module next to the importer takes precedence
+        test_files("std_shadow", &[("math.core", "cube: = 1;")], r#"import("math") cube:"#, "1");
+
+        // modules run in their own scope
+        test_files(
+            "isolated",
+            &[("m.core", r#"x: = 1;"#)],
+            r#"
+            x: = 10;
+            y: = 5;
+            import("m")
+            x: + y:
+            "#,
+            "6",
+        );
+    }
+
+    #[test]
+    fn module_search_path() {
+        let dir = write_files("search_path", &[("found.core", "")]);
+        let mut loader = ModuleLoader::new();
+        loader.set_search_path(vec![dir.clone()]);
+
+        assert_eq!(loader.resolve("found"), dir.join("found.core").canonicalize().ok());
+        assert_eq!(loader.resolve("missing_module"), None);
+    }
+
+    #[test]
+    fn module_cycles() {
+        let dir = write_files("cycle", &[("one.core", ""), ("two.core", "")]);
+        let mut loader = ModuleLoader::new();
+        loader.set_main(&dir.join("one.core").to_string_lossy());
+
+        let two = loader.resolve("two").unwrap();
+        let one = loader.resolve("one").unwrap();
+        assert!(loader.enter(&two).is_ok());
+
+        let err = loader.enter(&one).unwrap_err();
+        assert!(err.starts_with("circular import"));
+        assert!(err.contains("one.core -> "));
+        assert!(err.ends_with("one.core"));
+        assert!(err.contains("two.core"));
+    }
+
+    #[test]
+    fn export_stuff() {
+        let module = r#"
+            export([square shown])
+            square: = {x: ~ x:*x};
+            shown: = 7;
+            hidden: = 8;
+        "#;
+        test_files("export", &[("m.core", module)], r#"import("m") square(shown)"#, "49");
+
+        // reading names that are not exported is an error, see tests/scripts.rs
+        test_files(
+            "underscore",
+            &[("m.core", r#"_secret: = 1; open: = 2;"#)],
+            r#"import("m") open:"#,
+            "2",
+        );
+
+        // the importer can still define its own value for a private name
+        test_files(
+            "shadow",
+            &[("m.core", r#"export("a") a: = 1; b: = 2;"#)],
+            r#"import("m") b: = 3; a: + b:"#,
+            "4",
+        );
+
+        // fields of an imported block follow the same list
+        test_files(
+            "store",
+            &[("m.core", r#"export(shown) shown: = 5; hidden: = 6;"#)],
+            r#"m: = store_import("m"); m.shown:"#,
+            "5",
+        );
+    }
+
+    #[test]
+    fn prelude_stuff() {
+        test_std("neq(1 1)", "false");
+        test_std("neq(1 2)", "true");
+
+        test_std("geq(2 1)", "true");
+        test_std("geq(1 1)", "true");
+        test_std("geq(1 2)", "false");
+
+        test_std("leq(2 1)", "false");
+        test_std("leq(1 1)", "true");
+        test_std("leq(1 2)", "true");
+
+        test_std("square(7)", "49");
+        test_std("abs(neg(3))", "3");
+        test_std("abs(3)", "3");
+        test_std("sum([1 2 3 4])", "10");
+
+        test_std("max(3 5)", "5");
+        test_std("max(5 3)", "5");
+        test_std("min(3 5)", "3");
+        test_std("min(5 3)", "3");
+
+        test_std("clamp(0 15 10)", "10");
+        test_std("clamp(0 neg(5) 10)", "0");
+        test_std("clamp(0 5 10)", "5");
+
+        // prelude functions are visible inside other functions
+        test_std("f: = {x: ~ square(x)}; f(3)", "9");
+
+        // and can be shadowed by local definitions
+        test_std("square: = {x: ~ x}; square(3)", "3");
+    }
+
+    #[test]
+    fn string_functions() {
+        test_vm(r#"len("hello")"#, "5");
+        test_vm(r#"len("héllo wörld")"#, "11");
+        test_vm(r#"len([1 2 3])"#, "3");
+
+        test_vm(r#"substr("manticore" 3 6)"#, "tic");
+        test_vm(r#"substr("héllo" 1 3)"#, "él");
+        test_vm(r#"substr("short" 2 100)"#, "ort");
+
+        test_vm(r#"find("hello" "ll")"#, "2");
+        test_vm(r#"find("héllo" "l")"#, "2");
+        test_vm(r#"find("hello" "z")"#, "-1");
+
+        test_vm(r#"replace("a-b-c" "-" "+")"#, "a+b+c");
+
+        test_vm(r#"len(split("a b c" " "))"#, "3");
+        test_vm(r#"split("a b c" " ").1:"#, "b");
+        test_vm(r#"split("añb" "").1:"#, "ñ");
+        test_vm(r#"join(["a" "b" "c"] ", ")"#, "a, b, c");
+        test_vm(r#"join(split("1,2,3" ",") ";")"#, "1;2;3");
+        test_vm(r#"join([[1 2] [3]] ",")"#, "[1 2],[3]");
+
+        test_vm(r#"upper("straße")"#, "STRASSE");
+        test_vm(r#"lower("HeLLo")"#, "hello");
+        test_vm(r#"trim("  padded  ")"#, "padded");
+
+        test_vm(r#"starts_with("manticore" "man")"#, "true");
+        test_vm(r#"starts_with("manticore" "core")"#, "false");
+        test_vm(r#"ends_with("manticore" "core")"#, "true");
+
+        test_vm(r#"repeat("ab" 3)"#, "ababab");
+        test_vm(r#"reverse("héllo")"#, "olléh");
+
+        // string results are typed as strings
+        let mut lexer = lexer::Lexer::new_from_string(r#"concat("a" "b") upper("c")"#);
+        lexer.parse();
+        let mut parser = parser::Parser::new();
+        let mut vm = manticorevm::ManitcoreVm::new(&[], "");
+        for i in parser.shunt(&lexer.block_stack[0]).clone() {
+            vm.execute_token(&i);
+        }
+        for tok in &vm.execution_stack {
+            assert_eq!(tok.token_type, crate::token::TokenTypes::String);
+        }
+    }
+
+    #[test]
+    fn escape_stuff() {
+        test_vm(r#""a\nb""#, "a\nb");
+        test_vm(r#""tab\there""#, "tab\there");
+        test_vm(r#""back\\slash""#, "back\\slash");
+        test_vm(r#""say \"hi\"""#, "say \"hi\"");
+        test_vm(r#"'it\'s'"#, "it's");
+        test_vm(r#""caf\u{e9}""#, "café");
+        test_vm(r#"len("\u{1F600}")"#, "1");
+        test_vm(r#""a/b""#, "a/b");
+
+        // unknown escapes are reported instead of being kept
+        let mut lexer = lexer::Lexer::new_from_string(r#""bad \q" "\u{zz}""#);
+        lexer.parse();
+        assert_eq!(lexer.errors.len(), 2);
+        assert_eq!(lexer.errors[0].message, "unknown escape sequence \\q");
+        assert_eq!(lexer.errors[0].row, 5);
+    }
+
+    #[test]
+    fn interpolation_stuff() {
+        test_vm(r#"name: = "bob"; "hello {name}""#, "hello bob");
+        test_vm(r#"age: = 41; "next year {age: + 1}""#, "next year 42");
+        test_vm(r#"a: = 1; b: = 2; "{a} and {b}""#, "1 and 2");
+        test_vm(r#""list {[1 2 3]}""#, "list [1 2 3]");
+        test_vm(r#"x: = "in"; "{concat("out " "{x}")}""#, "out in");
+        test_vm(r#"f: = {x: ~ "got {x}"}; f(3)"#, "got 3");
+        test_std(r#""{square(4)}""#, "16");
+
+        // braces can be escaped and an empty pair is left alone
+        test_vm(r#""\{x\} {}""#, "{x} {}");
+
+        // an unfinished expression also leaves the string unterminated
+        let mut lexer = lexer::Lexer::new_from_string(r#""open {x""#);
+        lexer.parse();
+        assert_eq!(lexer.errors.len(), 2);
+        assert_eq!(lexer.errors[0].message, "unterminated interpolation in string");
+    }
+
+    #[test]
+    fn raw_and_multiline_strings() {
+        test_vm(r#"r"C:\new\{dir}""#, r"C:\new\{dir}");
+        test_vm(r#"r'a\'"#, r"a\");
+        test_vm("\"\"\"\n    one\n      two\n    \"\"\"", "one\n  two");
+        test_vm("x: = 3; \"\"\"\n  x is {x}\\t!\n  \"\"\"", "x is 3\t!");
+        test_vm("r'''\n  keep \\n {x}\n  '''", "keep \\n {x}");
+        test_vm(r#""""say "hi" now""""#, r#"say "hi" now"#);
+
+        // tokens after a string spanning lines know where they are
+        let mut lexer = lexer::Lexer::new_from_string("\"\"\"\n  a\n  b\n  \"\"\" after \"x\ny\" last");
+        lexer.parse();
+        let tokens = &lexer.block_stack[0];
+        assert_eq!(tokens[0].line_number, 1);
+        assert_eq!((tokens[1].line_number, tokens[1].row), (4, 6));
+        assert_eq!((tokens[3].line_number, tokens[3].row), (5, 3));
+    }
+
+    #[test]
+    fn unicode_lexing() {
+        test_vm("café: = 2; café: * 3", "6");
+        test_vm("变量: = 5; 变量:", "5");
+        test_vm("_ñ1: = 1; _ñ1:", "1");
+        test_vm("# ünïcödé 🎉 comment\n\"🎉 ok\"", "🎉 ok");
+        test_vm("x:\t=\t4; x:", "4");
 
+        // columns count characters and comments don't swallow lines
+        let mut lexer = lexer::Lexer::new_from_string("# é\n\"ü\" ñame §");
+        lexer.parse();
+        let tokens = &lexer.block_stack[0];
+        assert_eq!(tokens[1].value, "ñame");
+        assert_eq!((tokens[1].line_number, tokens[1].row), (2, 4));
+        assert_eq!(lexer.errors.len(), 1);
+        assert_eq!((lexer.errors[0].line_number, lexer.errors[0].row), (2, 9));
+    }
+
+    // Lexes the input and returns each error as (message, line, row)
+    fn lex_errors(input: &str) -> Vec<(String, usize, usize)> {
+        let mut lexer = lexer::Lexer::new_from_string(input);
+        lexer.parse();
+        lexer
+            .errors
+            .iter()
+            .map(|e| (e.message.clone(), e.line_number, e.row))
+            .collect()
+    }
+
+    #[test]
+    fn lexer_diagnostics() {
+        assert!(lex_errors("f: = {x: ~ [x 1]}; f(2)").is_empty());
+        assert_eq!(
+            lex_errors("f: = {x: ~\n  x;"),
+            vec![("unclosed '{'".to_string(), 1, 5)]
+        );
+        assert_eq!(
+            lex_errors("x: = [1 [2 3];"),
+            vec![("unclosed '['".to_string(), 1, 5)]
+        );
+        assert_eq!(
+            lex_errors("println(1)\n}"),
+            vec![("unexpected '}' with nothing to close".to_string(), 2, 0)]
+        );
+        assert_eq!(
+            lex_errors("{[1 2}]")[0],
+            ("'}' does not match the '[' opened on line 1".to_string(), 1, 5)
+        );
+        assert_eq!(
+            lex_errors("x: = 1;\nprintln(\"oops)"),
+            vec![("unterminated string".to_string(), 2, 8)]
+        );
+        assert_eq!(
+            lex_errors("'''\nnever closed"),
+            vec![("unterminated string".to_string(), 1, 0)]
+        );
+
+        // a stray brace no longer swallows the program
+        let mut lexer = lexer::Lexer::new_from_string("1 } 2");
+        lexer.parse();
+        assert_eq!(lexer.block_stack.len(), 1);
+        assert_eq!(lexer.block_stack[0].len(), 2);
+    }
+
+    #[test]
+    fn number_literals() {
+        test_vm("0xff", "255");
+        test_vm("0XFF + 1", "256");
+        test_vm("0b1010", "10");
+        test_vm("0o17", "15");
+        test_vm("1_000_000", "1000000");
+        test_vm("0xff_ff", "65535");
+        test_vm("1.5e-3", "0.0015");
+        test_vm("2E3", "2000");
+        test_vm("1e+2", "100");
+        test_vm("-5", "-5");
+        test_vm("3 * -2", "-6");
+        test_vm("f: = {x: ~ x}; f(-1.5)", "-1.5");
+        test_vm("[-1 -2 3].1:", "-2");
 
-}
\ No newline at end of file
+        // a minus right after a value subtracts, one with a space before it
+        // starts the next number
+        test_vm("x: = 7; x:-1", "6");
+        test_vm("8-2", "6");
+        test_vm("8 - 2", "6");
+        test_vm("x: = 5; x: - 1", "4");
+        test_vm("(8)-2", "6");
+        test_vm("f: = {x: y: ~ x: * y:}; f(5 -1)", "-5");
+        test_vm("f: = {x: ~ x}; f((-1))", "-1");
+        test_vm("[1 -1].1:", "-1");
+
+        for bad in ["0xzz", "1__0", "1_", "0b102", "12abc", "1e"] {
+            let mut lexer = lexer::Lexer::new_from_string(bad);
+            lexer.parse();
+            assert_eq!(lexer.errors.len(), 1, "{}", bad);
+            assert_eq!(lexer.errors[0].message, format!("invalid number literal {}", bad));
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        test_vm("1 < 2", "true");
+        test_vm("2 > 3", "false");
+        test_vm("2 <= 2", "true");
+        test_vm("1 >= 2", "false");
+        test_vm("1 + 2 == 3", "true");
+        test_vm("2 * 3 != 6", "false");
+        test_vm("1.0 == 1", "true");
+        test_vm(r#""apple" < "banana""#, "true");
+        test_vm(r#""1" == 1"#, "false");
+        test_vm("[1 2] == [1 2]", "true");
+        test_vm("x: = 5; x: + 1 > x:", "true");
+
+        // equ agrees with ==
+        test_vm("equ(1 1.0)", "true");
+        test_vm(r#"equ(1 "1")"#, "false");
+        test_vm("equ([1 2] [1 2])", "true");
+        test_std("neq(1 1.0)", "false");
+
+        // := still assigns, and comparisons can be assigned
+        test_vm("x:=5; x:", "5");
+        test_vm("x: = 3; y: = x: <= 4; y:", "true");
+        test_vm("x: = 5; if((x: > 3) {1} {2})", "1");
+    }
+
+    #[test]
+    fn modulo_division_and_power() {
+        test_vm("7 % 3", "1");
+        test_vm("-7 % 3", "-1");
+        test_vm("7.5 % 2", "1.5");
+        test_vm("7 // 2", "3");
+        test_vm("-7 // 2", "-4");
+        test_vm("2 ^ 10", "1024");
+        test_vm("2 ^ 3 ^ 2", "512");
+        test_vm("2 * 3 ^ 2", "18");
+        test_vm("2 ^ 3 * 2", "16");
+        test_vm("10 - 2 ^ 3", "2");
+        test_vm("1 + 10 % 4 * 2", "5");
+        test_vm("x: = 9; x: // 2 == 4", "true");
+        test_vm("pow(2 3)", "8");
+    }
+
+    #[test]
+    fn boolean_operators() {
+        test_vm("true && false", "false");
+        test_vm("true && true", "true");
+        test_vm("false || true", "true");
+        test_vm("false || false", "false");
+        test_vm("!true", "false");
+        test_vm("!!true", "true");
+        test_vm("!false && true", "true");
+        test_vm("1 < 2 && 3 > 2", "true");
+        test_vm("1 > 2 || 2 == 2 && !false", "true");
+        test_vm("true || false && false", "true");
+        test_vm("f: = {n: ~ n: > 0 && n: < 10}; f(50)", "false");
+
+        // the right side only runs when it is needed, a bad
+        // addition here would end the process
+        test_vm(r#"false && 1 + "a" == 2"#, "false");
+        test_vm(r#"true || 1 + "a" == 2"#, "true");
+        test_vm("y: = _; exist(y) && y.len: > 0", "false");
+        test_vm("y: = [1 2]; exist(y) && y.len: > 1", "true");
+    }
+
+    #[test]
+    fn unary_minus() {
+        test_vm("x: = 4; -x:", "-4");
+        test_vm("x: = 4; 3 * -x:", "-12");
+        test_vm("- 5", "-5");
+        test_vm("-(1 + 2)", "-3");
+        test_vm("x: = 4; 10 - -x:", "14");
+        test_vm("x: = 4; -x: * 2 + 1", "-7");
+        test_vm("x: = 4; +x: + 1", "5");
+        test_vm("x: = 4; x: - 1", "3");
+        test_vm("f: = {n: ~ -n:}; f(3)", "-3");
+
+        // signs bind looser than powers
+        test_vm("-2 ^ 2", "-4");
+        test_vm("x: = 2; -x: ^ 2", "-4");
+        test_vm("3 * -2 ^ 2", "-12");
+        test_vm("2 ^ -1", "0.5");
+
+        // the stack is left with just the result
+        let mut lexer = lexer::Lexer::new_from_string("x: = 4; 3 * -x:");
+        lexer.parse();
+        let mut parser = parser::Parser::new();
+        let mut vm = manticorevm::ManitcoreVm::new(&[], "");
+        for i in parser.shunt(&lexer.block_stack[0]).clone() {
+            vm.execute_token(&i);
+        }
+        assert_eq!(vm.execution_stack.len(), 1);
+    }
+
+    fn format(input: &str) -> String {
+        let mut lexer = lexer::Lexer::new_from_string(input);
+        lexer.parse();
+        let tree = crate::ast::parse(&lexer.block_stack[0]).unwrap();
+        crate::ast::pretty_file(tree, &lexer.comments)
+    }
+
+    #[test]
+    fn ast_and_pretty_printer() {
+        use crate::ast::{parse, Expr};
+
+        // layout is canonical
+        assert_eq!(format("x:=5;y:=x:*2+1"), "x: = 5;\ny: = x: * 2 + 1\n");
+        assert_eq!(format("sq: = {x:~x:*x}"), "sq: = {x: ~ x: * x}\n");
+        assert_eq!(format("if equ(1 1) {true} {false};"), "if equ(1 1) {true} {false};\n");
+        assert_eq!(format("print(\"a\\n{1 + 2}\\{\")"), "print(\"a\\n{1 + 2}\\{\")\n");
+        assert_eq!(format("0x10 -2 ^ 2 (1 + 2) * 3"), "16\n-2 ^ 2\n(1 + 2) * 3\n");
+        assert_eq!(format("do.run(\"a\",\"b\") {x: = 1;}.x: @{1}"), "do.run(\"a\", \"b\")\n{x: = 1;}.x:\n@{1}\n");
+        assert_eq!(
+            format("f: = {a: b: ~ c: = a: + b:; c: * 2}\nf(1 2)"),
+            "f: = {\n    a: b: ~\n    c: = a: + b:;\n    c: * 2\n}\n\nf(1 2)\n"
+        );
+
+        // precedence and associativity come out of the tree
+        let mut lexer = lexer::Lexer::new_from_string("1 + 2 * 3 ^ 2 ^ 2 == a: && !b:");
+        lexer.parse();
+        let tree = parse(&lexer.block_stack[0]).unwrap();
+        assert_eq!(tree.len(), 1);
+        match &tree[0].node.expr {
+            Expr::Binary { op, right, .. } => {
+                assert_eq!(op, "&&");
+                assert!(matches!(&right.expr, Expr::Unary { op, .. } if op == "!"));
+            }
+            other => panic!("expected &&, got {:?}", other),
+        }
+        assert_eq!(tree[0].node.span.row, 0);
+        assert_eq!(tree[0].node.span.end_row, 28);
+
+        // formatting is stable and keeps the meaning of the program
+        for source in [
+            include_str!("../std/list.core"),
+            include_str!("../std/math.core"),
+            include_str!("../std/string.core"),
+            include_str!("../std/io.core"),
+        ] {
+            let once = format(source);
+            assert_eq!(format(&once), once);
+            assert_eq!(once.matches('#').count(), source.matches('#').count());
+        }
+        test_vm(&format("x:=2;y:={n:~n:*10}; y(x: + 1) - -x:"), "32");
+
+        // broken code is reported where it goes wrong
+        let mut lexer = lexer::Lexer::new_from_string("x: = (1 + 2");
+        lexer.parse();
+        let error = parse(&lexer.block_stack[0]).unwrap_err();
+        assert_eq!(error.message, "missing ')'");
+        assert_eq!((error.line_number, error.row), (1, 5));
+    }
+
+    #[test]
+    fn pretty_printer_keeps_comments() {
+        let source = "# header\nx:=1; # one\n\nf: = {a:~\n  # inside\n  a:*2 # double\n  # last\n};\ny: = [1 # list\n 2]\n# end\n";
+        let once = format(source);
+        assert_eq!(
+            once,
+            "# header\nx: = 1; # one\n\nf: = {\n    a: ~\n    # inside\n    a: * 2 # double\n    # last\n};\n\n# list\ny: = [1 2]\n# end\n"
+        );
+        assert_eq!(format(&once), once);
+        test_vm(&format("x:=2; # two\nx: * 3 # six"), "6");
+    }
+
+    #[test]
+    fn nested_assignment() {
+        test_vm("dog: = {name: = \"rex\"; age: = 3;}; dog.name: = \"max\"; dog.name:", "max");
+        test_vm("dog: = {name: = \"rex\"; age: = 3;}; dog.name: = \"max\"; dog.age:", "3");
+        test_vm("dog: = {name: = \"rex\";}; dog.owner: = \"sam\"; dog.owner:", "sam");
+        test_vm("list: = [1 2 3]; list.2: = 9; list.2:", "9");
+        test_vm("list: = [1 2 3]; list.2: = 9; list.0:", "1");
+        test_vm("m: = [[1 2] [3 4]]; m.1.0: = 7; m.1.0:", "7");
+        test_vm("a: = {items: = [{name: = \"a\";} 5];}; a.items.0.name: = \"z\"; a.items.0.name:", "z");
+        test_vm("a: = {items: = [{name: = \"a\";} 5];}; a.items.1: = 6; a.items.1:", "6");
+        test_vm("a: = {items: = [1];}; a.items.0: = 2 + 3; a.items.0:", "5");
+    }
+
+    #[test]
+    fn negative_indexes_and_slices() {
+        test_vm("l: = [1 2 3 4 5]; l.-1:", "5");
+        test_vm("l: = [1 2 3 4 5]; l.-5:", "1");
+        test_vm("l: = [1 2 3 4 5]; i: = 0 - 2; l.i:", "4");
+        test_vm("l: = [1 2 3]; l.-1: = 9; l.2:", "9");
+        test_vm("s: = \"héllo\"; s.1:", "é");
+        test_vm("\"abc\".-1:", "c");
+
+        test_vm("l: = [1 2 3 4 5]; join(slice(l: 1 -1) \",\")", "2,3,4");
+        test_vm("l: = [1 2 3 4 5]; join(slice(l: _ 2) \",\")", "1,2");
+        test_vm("l: = [1 2 3 4 5]; join(slice_step(l: _ _ 2) \",\")", "1,3,5");
+        test_vm("l: = [1 2 3 4 5]; join(slice_step(l: -1 0 -2) \",\")", "5,3");
+        test_vm("l: = [1 2 3 4 5]; join(slice_step(l: _ _ -1) \",\")", "5,4,3,2,1");
+        test_vm("l: = [1 2 3]; slice(l: 5 10).len:", "0");
+        test_vm("slice(\"hello\" 1 _)", "ello");
+        test_vm("slice(\"hello\" -3 -1)", "ll");
+        test_vm("slice_step(\"hello\" _ _ -1)", "olleh");
+        test_vm("slice(\"hi\" -10 10)", "hi");
+    }
+
+    #[test]
+    fn higher_order_list_functions() {
+        test_vm("l: = [1 2 3 4]; join(map(l: {x: ~ x: * x}) \",\")", "1,4,9,16");
+        test_vm("sq: = {x: ~ x: * x}; join(map([1 2] sq:) \",\")", "1,4");
+        test_vm("l: = [1 2 3 4]; join(filter(l: {x: ~ x: % 2 == 0}) \",\")", "2,4");
+        test_vm("reduce([1 2 3 4] {a: b: ~ a: + b:})", "10");
+        test_vm("reduce([\"a\" \"b\" \"c\"] {a: b: ~ concat(a: b:)})", "abc");
+        test_vm("fold([1 2 3] 10 {a: b: ~ a: + b:})", "16");
+        test_vm("fold([] 10 {a: b: ~ a: + b:})", "10");
+        test_vm("any([1 2 3] {x: ~ x: > 2})", "true");
+        test_vm("any([] {x: ~ x: > 2})", "false");
+        test_vm("all([1 2 3] {x: ~ x: > 2})", "false");
+        test_vm("all([3 4] {x: ~ x: > 2})", "true");
+        test_vm("find([1 2 3] {x: ~ x: > 1})", "2");
+        test_vm("exist(find([1 2 3] {x: ~ x: > 5}))", "false");
+        test_vm("find(\"hello\" \"l\")", "2");
+        test_vm("zip([1 2 3] [\"a\" \"b\"]).len:", "2");
+        test_vm("zip([1 2 3] [\"a\" \"b\"]).1.1:", "b");
+        test_vm("enumerate([\"a\" \"b\"]).1.0:", "1");
+        test_vm("enumerate([\"a\" \"b\"]).1.1:", "b");
+        test_vm("flatten([[1 2] 3 [4 [5]]]).len:", "5");
+        test_vm("join(take([1 2 3] 2) \",\")", "1,2");
+        test_vm("join(drop([1 2 3] 2) \",\")", "3");
+        test_vm("take([1 2 3] 10).len:", "3");
+        test_vm("drop([1 2 3] 10).len:", "0");
+    }
+
+    #[test]
+    fn sorting() {
+        test_vm("join(sort([3 1 2 10]) \",\")", "1,2,3,10");
+        test_vm("join(sort([\"pear\" \"apple\" \"fig\"]) \",\")", "apple,fig,pear");
+        test_vm("\"{sort([\"b\" 2 _ true \"a\" 1 [2] [1 5]])}\"", "[_ true 1 2 a b [1 5] [2]]");
+        test_vm("join(sort([-1.5 2 -3]) \",\")", "-3,-1.5,2");
+        test_vm("join(sort_by([\"ccc\" \"a\" \"bb\"] {s: ~ len(s:)}) \",\")", "a,bb,ccc");
+        test_vm("join(sort_by([3 1 2] {x: ~ -x:}) \",\")", "3,2,1");
+        test_vm("join(sort_with([3 1 2] {a: b: ~ b: - a:}) \",\")", "3,2,1");
+
+        // ties keep their original order
+        test_vm(
+            "p: = sort_by([[\"b\" 1] [\"a\" 2] [\"c\" 1] [\"d\" 2]] {x: ~ x.1:}); \"{p}\"",
+            "[[b 1] [c 1] [a 2] [d 2]]",
+        );
+        test_vm(
+            "p: = sort_with([[\"b\" 1] [\"a\" 2] [\"c\" 1]] {x: y: ~ x.1: - y.1:}); \"{p}\"",
+            "[[b 1] [c 1] [a 2]]",
+        );
+
+        test_vm("join(reverse([1 2 3]) \",\")", "3,2,1");
+        test_vm("reverse(\"abc\")", "cba");
+        test_vm("l: = [1 2]; reverse(l:); l.0:", "1");
+
+        // other collections reverse into a list of their items
+        test_vm("\"{reverse(range(1 3))}\"", "[3 2 1]");
+        test_vm("\"{reverse(deque([1 2 3]))}\"", "[3 2 1]");
+        test_vm("\"{reverse(set_of([3 1 2]))}\"", "[3 2 1]");
+        test_vm("\"{reverse(min_queue([3 1 2] _))}\"", "[3 2 1]");
+    }
+
+    #[test]
+    fn iteration_protocol() {
+        test_vm("s: = \"\"; for c: \"héy\" {s: = concat(c: s:);}; s:", "yéh");
+        test_vm("s: = \"\"; for [i x] [\"a\" \"b\"] {s: = concat(s: \"{i}{x}\");}; s:", "0a1b");
+        test_vm("s: = \"\"; for [i c] \"ab\" {s: = concat(s: \"{i}{c}\");}; s:", "0a1b");
+
+        // objects without next are read as maps, in key order
+        test_vm(
+            r#"
+            dog: = {name: = "rex"; age: = 3;};
+            s: = "";
+            for [k v] dog: {s: = concat(s: "{k}={v} ");};
+            s:
+            "#,
+            "age=3 name=rex ",
+        );
+        test_vm(
+            "dog: = {name: = \"rex\";}; s: = \"\"; for p: dog: {s: = \"{p}\";}; s:",
+            "[name rex]",
+        );
+
+        // objects with next hand out items until next gives back _
+        test_vm(
+            r#"
+            counter: = {n: = 0; next: = {if lss(n: 3) {n: = n: + 1; n:} {_}};};
+            total: = 0;
+            for x: counter: {total: = total: + x:;};
+            for x: counter: {total: = total: + x:;};
+            total:
+            "#,
+            "12",
+        );
+        test_vm(
+            r#"
+            count_to: = {limit: ~ n: = 0; next: = {if lss(n: limit:) {n: = n: + 1; n:} {_}}; self};
+            s: = "";
+            for [i x] count_to(2) {s: = concat(s: "{i}:{x} ");};
+            s:
+            "#,
+            "0:1 1:2 ",
+        );
+        test_vm(
+            r#"
+            forever: = {n: = 0; next: = {n: = n: + 1; n:};};
+            last: = 0;
+            for x: forever: {if gtr(x: 4) {break}; last: = x:;};
+            last:
+            "#,
+            "4",
+        );
+    }
+
+    #[test]
+    fn lazy_ranges_and_generators() {
+        test_vm("\"{collect(range(1 4))}\"", "[1 2 3 4]");
+        test_vm("\"{collect(range(3 1))}\"", "[3 2 1]");
+        test_vm("\"{collect(range_step(0 1 0.25))}\"", "[0 0.25 0.5 0.75 1]");
+        test_vm("\"{collect(range_step(10 0 -5))}\"", "[10 5 0]");
+        test_vm("\"{collect(range_step(0 10 -1))}\"", "[]");
+        test_vm("\"{range(1 5)}\"", "range(1 5 1)");
+
+        // nothing is made up front
+        test_vm("range(0 100000000).len:", "100000001");
+        test_vm("range(0 100000000).-1:", "100000000");
+        test_vm("range(5 1).1:", "4");
+        test_vm("\"{take(range(0 100000000) 3)}\"", "[0 1 2]");
+        test_vm("any(range(0 100000000) {x: ~ x: > 5})", "true");
+
+        test_vm("total: = 0; for x: range(1 4) {total: = total: + x:;}; total:", "10");
+        test_vm("reduce(range(1 100) {a: b: ~ a: + b:})", "5050");
+        test_vm("\"{map(range(1 3) {x: ~ x: * x})}\"", "[1 4 9]");
+
+        // generators run their block on the last value until it gives _
+        test_vm("d: = generate(1 {x: ~ x: * 2}); \"{take(d: 5)}\"", "[1 2 4 8 16]");
+        test_vm("d: = generate(1 {x: ~ x: * 2}); find(d: {x: ~ x: > 100})", "128");
+        test_vm("d: = generate(1 {x: ~ x: * 2}); \"{zip(d: ['a' 'b'])}\"", "[[1 a] [2 b]]");
+        test_vm(
+            "u: = generate(1 {x: ~ if lss(x: 3) {x: + 1} {_}}); \"{collect(u:)}\"",
+            "[1 2 3]",
+        );
+        test_vm(
+            "u: = generate(1 {x: ~ if lss(x: 3) {x: + 1} {_}}); s: = 0; for x: u: {s: = s: + x:;}; s:",
+            "6",
+        );
+
+        // dropping from a generator gives a generator
+        test_vm("d: = generate(1 {x: ~ x: * 2}); \"{take(drop(d: 3) 2)}\"", "[8 16]");
+        test_vm("u: = generate(1 {x: ~ if lss(x: 3) {x: + 1} {_}}); \"{collect(drop(u: 1))}\"", "[2 3]");
+        test_vm("u: = generate(1 {x: ~ if lss(x: 3) {x: + 1} {_}}); \"{collect(drop(u: 5))}\"", "[]");
+
+        // the list builtins that change a list work on the items
+        test_vm("\"{push(range(1 3) 9)}\"", "[1 2 3 9]");
+        test_vm("push(range(1 3) 9).len:", "4");
+        test_vm("r: = pop(range(1 3) x); \"{x} {r}\"", "3 [1 2]");
+        test_vm("\"{insert(range(1 3) 0 1)}\"", "[1 0 2 3]");
+        test_vm("\"{remove(range(1 3) 0)}\"", "[2 3]");
+        test_vm("\"{append([0] range(1 2))}\"", "[0 1 2]");
+        test_vm(
+            "u: = generate(1 {x: ~ if lss(x: 3) {x: + 1} {_}}); \"{push(u: 4)}\"",
+            "[1 2 3 4]",
+        );
+    }
+
+    #[test]
+    fn bare_names() {
+        // bare names in a call go out after the other arguments, last first
+        test_vm("a: = 5; b: = 2; gtr(a b)", "false");
+        test_vm("a: = 'x'; b: = 'y'; concat(a b)", "yx");
+        test_vm("a: = 'x'; concat(a 'z')", "zx");
+        test_vm("a: = 'x'; b: = 'y'; concat(a: b:)", "xy");
+        test_vm("x: = 3; x * x", "9");
+
+        // in a comprehension they are read where they are written
+        test_vm("\"{[gtr(x 2) for x: [1 3]]}\"", "[false true]");
+        test_vm("b: = 'y'; \"{[concat(a b) for a: ['x']]}\"", "[xy]");
+    }
+
+    #[test]
+    fn list_comprehensions() {
+        test_vm("xs: = [-1 2 -3 4]; \"{[x * x for x: xs if gtr(x 0)]}\"", "[4 16]");
+        test_vm("xs: = [-1 2 -3 4]; \"{[x: + 1 for x: xs]}\"", "[0 3 -2 5]");
+        test_vm("\"{[x * 10 for x: range(1 3)]}\"", "[10 20 30]");
+        test_vm("\"{[c for c: 'abc']}\"", "[a b c]");
+        test_vm("\"{[concat(k ':') for [k v] {a: = 1; b: = 2;}]}\"", "[a: b:]");
+        test_vm("xs: = [-1 2 -3 4]; \"{[i for [i x] xs if lss(x 0)]}\"", "[0 2]");
+        test_vm("n: = 100; \"{[x: + n: for x: [1 2]]}\"", "[101 102]");
+        test_vm(
+            "\"{[[y for y: range(1 x)] for x: range(1 3)]}\"",
+            "[[1] [1 2] [1 2 3]]",
+        );
+        test_vm("\"{[x for x: [] if x]}\"", "[]");
+
+        // plain lists are left alone
+        test_vm("\"{[1 2 3]}\"", "[1 2 3]");
+    }
+
+    #[test]
+    fn sets() {
+        // sets keep their items in order so they can be searched
+        test_vm("\"{set_of([3 1 3 2 1])}\"", "set_of([1 2 3])");
+        test_vm("\"{set_of(['b' 2 [1] 'a' 1])}\"", "set_of([1 2 a b [1]])");
+        test_vm("set_of([1 1.0 '1' [1 2] [1.0 2]]).len:", "3");
+        test_vm("\"{set_of('hello')}\"", "set_of([e h l o])");
+
+        // whole numbers are kept exactly, past what an f32 can hold
+        test_vm("set_of([16777216 16777217]).len:", "2");
+        test_vm("set_of([16777216 16777216.0]).len:", "1");
+        test_vm("contains(set_of([16777216]) 16777217)", "false");
+        test_vm("equ(16777216 16777217)", "false");
+        test_vm("\"{sort([16777217 16777216])}\"", "[16777216 16777217]");
+        test_vm("\"{set_of(range(1 3))}\"", "set_of([1 2 3])");
+        test_vm("\"{set_of([])}\"", "set_of([])");
+
+        test_vm("s: = add(set_of([1 2]) 3); \"{add(s: 1)}\"", "set_of([1 2 3])");
+        test_vm("s: = add(set_of([2 3]) 1); \"{add(s: 2.0)}\"", "set_of([1 2 3])");
+        test_vm("contains(add(set_of([]) [1 'a']) [1.0 'a'])", "true");
+        test_vm("\"{remove(set_of([1 2 3]) 2)}\"", "set_of([1 3])");
+        test_vm("\"{remove(set_of([1 2 3]) 9)}\"", "set_of([1 2 3])");
+        test_vm("contains(set_of([[1 2] 'a']) [1 2])", "true");
+        test_vm("contains(set_of([1 2]) 3)", "false");
+        test_vm("contains([1 2 3] 2.0)", "true");
+        test_vm("contains('hello' 'ell')", "true");
+        test_vm("contains(range(1 100000000) 5)", "true");
+
+        test_vm("\"{union(set_of([1 2]) set_of([2 3]))}\"", "set_of([1 2 3])");
+        test_vm("\"{intersection(set_of([1 2 3]) [2 3 4])}\"", "set_of([2 3])");
+        test_vm("\"{difference(set_of([1 2 3]) set_of([2]))}\"", "set_of([1 3])");
+
+        // order does not matter when sets are compared
+        test_vm("equ(set_of([1 2]) set_of([2 1]))", "true");
+        test_vm("equ(add(set_of([]) set_of([1 2])) add(set_of([]) set_of([2 1])))", "true");
+        test_vm("equ(add(set_of([]) set_of([1 2])) add(set_of([]) set_of([3])))", "false");
+        test_vm("equ([1 [2]] [1.0 [2.0]])", "true");
+
+        test_vm("total: = 0; for x: set_of([1 2 2 3]) {total: = total: + x:;}; total:", "6");
+        test_vm("\"{map(set_of([1 2 2]) {x: ~ x: * 10})}\"", "[10 20]");
+    }
+
+    #[test]
+    fn queues_and_sorted_maps() {
+        test_vm("d: = deque([2 3]); d: = push_front(d: 1); \"{push(d: 4)}\"", "deque([1 2 3 4])");
+        test_vm("d: = pop_front(deque([1 2 3]) a); \"{a} {d}\"", "1 deque([2 3])");
+        test_vm("d: = pop(deque([1 2 3]) b); \"{b} {d}\"", "3 deque([1 2])");
+        test_vm("d: = deque('abc'); \"{peek(d:)} {d.-1} {d.len}\"", "a c 3");
+        test_vm("peek(deque([]))", "_");
+
+        // queues hand out the top priority first, ties in the order they came
+        test_vm("q: = push(min_queue([5 1 4] _) 2); \"{q}\"", "min_queue([1 2 4 5])");
+        test_vm("q: = pop(min_queue([5 1 4] _) top); \"{top} {q} {q.len}\"", "1 min_queue([4 5]) 2");
+        test_vm("\"{max_queue(range(1 4) _)}\"", "max_queue([4 3 2 1])");
+        test_vm(
+            "q: = max_queue([['a' 2] ['b' 5] ['c' 2] ['d' 5]] {x: ~ x.1}); s: = ''; for x: q: {s: = concat(s: x.0);}; s:",
+            "bdac",
+        );
+        test_vm("q: = min_queue([[2 'x'] [1 'y']] {x: ~ x.0}); peek(q:).1:", "y");
+        test_vm("q: = min_queue([3 1 2] _); q.0:", "1");
+
+        test_vm("m: = sorted_map([['b' 2] ['a' 1]]); \"{put(m: 'c' 3)}\"", "sorted_map([[a 1] [b 2] [c 3]])");
+        test_vm("m: = put(sorted_map([[1 'x']]) 1.0 'y'); \"{m} {m.len}\"", "sorted_map([[1 y]]) 1");
+        test_vm("m: = sorted_map([['a' 1]]); get(m: 'a')", "1");
+        test_vm("m: = sorted_map([['a' 1]]); get(m: 'b')", "_");
+        test_vm("m: = sorted_map([['a' 1]]); contains(m: 'a')", "true");
+        test_vm("\"{remove(sorted_map([[2 'b'] [1 'a']]) 2)}\"", "sorted_map([[1 a]])");
+        test_vm("\"{sorted_map({y: = 2; x: = 1;})}\"", "sorted_map([[x 1] [y 2]])");
+        test_vm(
+            "s: = ''; for [k v] sorted_map([['b' 2] ['a' 1]]) {s: = concat(s: k);}; s:",
+            "ab",
+        );
+    }
+
+    #[test]
+    fn keyed_collections_keep_their_order() {
+        // the generic mutators go through each collection's own insert
+        test_vm("\"{push(sorted_map([[2 1]]) [1 5])}\"", "sorted_map([[1 5] [2 1]])");
+        test_vm("\"{insert(sorted_map([[2 1]]) [2 7] 0)}\"", "sorted_map([[2 7]])");
+        test_vm("\"{push_front(min_queue([3 1] _) 0)}\"", "min_queue([0 1 3])");
+        test_vm("\"{append(max_queue([3] _) [1 5])}\"", "max_queue([5 3 1])");
+        test_vm("\"{push_front(set_of([3 1]) 2)}\"", "set_of([1 2 3])");
+        test_vm("\"{append(set_of([3]) deque([1 3]))}\"", "set_of([1 3])");
+
+        // a queue is a heap, it pops in order whatever order items came in
+        test_vm(
+            "q: = min_queue([9 4 7 1 8 2 6 3 5 0] _); s: = ''; for n: range(1 10) {q: = pop(q: x); s: = concat(s: x);}; s:",
+            "0123456789",
+        );
+        test_vm(
+            "q: = min_queue([] {x: ~ x % 2}); for x: range(0 5) {q: = push(q: x);}; s: = ''; for n: range(1 6) {q: = pop(q: x); s: = concat(s: x);}; s:",
+            "024135",
+        );
+
+        // a deque pushed and popped at the front keeps its items in order
+        test_vm(
+            "d: = deque([]); for x: range(0 4) {d: = push_front(d: x);}; \"{d} {d.len} {d.0} {d.-1}\"",
+            "deque([4 3 2 1 0]) 5 4 0",
+        );
+        test_vm(
+            "d: = deque(range(0 5)); d: = pop_front(d: a); d: = pop_front(d: b); d: = pop_front(d: c); d: = push_front(d: 9); \"{d} {peek(d:)} {d.len}\"",
+            "deque([9 3 4 5]) 9 4",
+        );
+        test_vm("d: = pop_front(deque([1 2 3]) a); \"{insert(d: 7 1)} {remove(d: 0)}\"", "deque([2 7 3]) deque([3])");
+        test_vm("join(pop_front(deque('abc') a) '-')", "b-c");
+    }
+
+    #[test]
+    fn shadowing_builtins() {
+        // names of builtins can be bound like any other name
+        test_vm("len: = {x: ~ 42}; len('abc')", "42");
+        test_vm("f: = {find: ~ find: + 1}; f(2)", "3");
+        test_vm("join: = 5; join:", "5");
+        test_vm("len('abc')", "3");
+        test_std("clamp(1 7 5)", "5");
+    }
+
+    #[test]
+    fn lowering_to_postfix() {
+        fn lower(input: &str) -> String {
+            let mut lexer = lexer::Lexer::new_from_string(input);
+            lexer.parse();
+            let mut parser = parser::Parser::new();
+            let tokens: Vec<String> = parser
+                .shunt(&lexer.block_stack[0])
+                .iter()
+                .map(|t| t.value.clone())
+                .collect();
+            tokens.join(" ")
+        }
+
+        // the vm runs the syntax tree with operands first
+        assert_eq!(lower("x: = 1 + 2 * 3;"), "x 1 2 3 * + =");
+        assert_eq!(lower("f(a: b)"), "a b f @");
+        assert_eq!(lower("len('ab')"), "ab len");
+        assert_eq!(lower("-2 ^ 2"), "2 2 ^ neg");
+        assert_eq!(lower("a.b.0: = 1"), "a.b.0 1 =");
+        assert_eq!(lower("dog.name:"), "dog name .");
+        assert_eq!(lower("if x: {1} {2};"), "x block block if");
+        assert_eq!(lower("print(1, 2)"), "1 print 2 print");
+        assert_eq!(lower("a: && b:"), "a &&");
+        test_vm("f: = {x: ~ concat(x: '!')}; f('a', 'b')", "b!");
+    }
+}
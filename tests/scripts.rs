@@ -0,0 +1,168 @@
+use std::process::Command;
+
+// Writes the modules and main.core into their own directory and runs
+// main.core with the interpreter, giving back whether it succeeded and
+// what it printed
+fn run_files(dir: &str, files: &[(&str, &str)], main: &str) -> (bool, String) {
+    let dir = std::env::temp_dir()
+        .join(format!("manticore_scripts_{}", std::process::id()))
+        .join(dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, source) in files {
+        std::fs::write(dir.join(name), source).unwrap();
+    }
+    let script = dir.join("main.core");
+    std::fs::write(&script, main).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_manticore"))
+        .arg("--no-prelude")
+        .arg(&script)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+    )
+}
+
+fn assert_error(dir: &str, files: &[(&str, &str)], main: &str, message: &str) {
+    let (success, output) = run_files(dir, files, main);
+    assert!(!success, "expected {:?} to fail, it printed {}", main, output);
+    assert!(output.contains(message), "{:?} not found in {}", message, output);
+}
+
+#[test]
+fn private_names() {
+    let module = r#"
+        export([shown])
+        shown: = 7;
+        hidden: = 8;
+    "#;
+    assert_error(
+        "hidden",
+        &[("m.core", module)],
+        r#"import("m") println(hidden:)"#,
+        "hidden is not exported by module",
+    );
+
+    // without export, names starting with an underscore are private
+    assert_error(
+        "underscore",
+        &[("m.core", r#"_secret: = 1; open: = 2;"#)],
+        r#"import("m") println(_secret:)"#,
+        "_secret is not exported by module",
+    );
+
+    // defining the name in the importer is still allowed
+    let (success, output) = run_files(
+        "shadow",
+        &[("m.core", module)],
+        r#"import("m") hidden: = 3; println(hidden: + shown:)"#,
+    );
+    assert!(success, "{}", output);
+    assert_eq!(output.trim(), "10");
+
+    // and so is binding it in a loop, a comprehension or a pop
+    let (success, output) = run_files(
+        "binders",
+        &[("m.core", "export([f]) f: = 0; i: = 0;")],
+        r#"
+        import("m")
+        for i: [1 2] {println(i:)};
+        println("{[i * 3 for i: [1 2]]}")
+        q: = [7 8];
+        pop(q: i)
+        println(i:)
+        "#,
+    );
+    assert!(success, "{}", output);
+    assert_eq!(output, "1\n2\n[3 6]\n8\n");
+}
+
+#[test]
+fn object_field_writes() {
+    // the bodies run once when first written to, later writes and reads
+    // see only the fields
+    let (success, output) = run_files(
+        "fields",
+        &[],
+        r#"
+        o: = {println("outer"); n: = 0; inner: = {println("inner"); m: = 0;};};
+        o.n: = 1;
+        o.inner.m: = 2;
+        for x: range(1 300) {o.n: = o.n: + 1;};
+        o.inner.m: = o.inner.m: + 1;
+        println(o.n:)
+        println(o.inner.m:)
+        "#,
+    );
+    assert!(success, "{}", output);
+    assert_eq!(output.matches("outer").count(), 1, "{}", output);
+    assert_eq!(output.matches("inner").count(), 1, "{}", output);
+    assert!(output.ends_with("301\n3\n"), "{}", output);
+}
+
+#[test]
+fn long_generators() {
+    // a generator runs until it ends, however many items that takes
+    let (success, output) = run_files(
+        "long",
+        &[],
+        "println(len(collect(generate(1 {x: ~ if x: < 100001 {x: + 1} {_};}))))",
+    );
+    assert!(success, "{}", output);
+    assert_eq!(output.trim(), "100001");
+}
+
+#[test]
+fn keyed_collection_mutators() {
+    // a sorted map only takes [key value] pairs and a queue only pops
+    assert_error(
+        "map_push",
+        &[],
+        "m: = push(sorted_map([[1 1]]) 7); println(get(m: 5));",
+        "push expected a [key value] pair for a sorted map, got 7",
+    );
+    assert_error(
+        "queue_remove",
+        &[],
+        "println(remove(min_queue([3 1] _) 0));",
+        "cannot remove by index from a queue, use pop",
+    );
+}
+
+#[test]
+fn parse_errors() {
+    // nothing runs when the program does not parse
+    assert_error(
+        "unclosed",
+        &[],
+        "println(\"ran\"); x: = (1 + 2",
+        "missing ')'",
+    );
+    let (ok, output) = run_files("unclosed_output", &[], "println(\"ran\"); x: = (1 + 2");
+    assert!(!ok);
+    assert!(!output.contains("ran\n"), "{}", output);
+
+    // and neither does a program importing a module that does not parse
+    let bad = [("bad.core", "x: = (1 + 2")];
+    for import in ["import", "store_import"] {
+        let main = format!("{}(\"bad\") println(\"still running\")", import);
+        let (ok, output) = run_files(import, &bad, &main);
+        assert!(!ok, "{}", output);
+        assert!(output.contains("missing ')'"), "{}", output);
+        assert!(!output.contains("still running"), "{}", output);
+    }
+}
+
+#[test]
+fn string_builtins_take_strings() {
+    assert_error("upper", &[], "println(upper([1 2]))", "upper expected a string, got [1 2]");
+    assert_error(
+        "substr",
+        &[],
+        "println(substr([1 2 3] 0 1))",
+        "substr expected a string, got [1 2 3]",
+    );
+    assert_error("len", &[], "println(len(5))", "cannot take the length of 5");
+}
@@ -0,0 +1,42 @@
+use std::process::Command;
+
+// Runs one of the standard library test scripts with the interpreter,
+// a failed assert makes it exit with an error
+fn test_std_module(name: &str) {
+    let script = format!("{}/std/tests/{}_test.core", env!("CARGO_MANIFEST_DIR"), name);
+
+    // Scripts that touch files do so in their own directory
+    let dir = std::env::temp_dir().join(format!("manticore_std_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_manticore"))
+        .arg(&script)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn std_math() {
+    test_std_module("math");
+}
+
+#[test]
+fn std_list() {
+    test_std_module("list");
+}
+
+#[test]
+fn std_string() {
+    test_std_module("string");
+}
+
+#[test]
+fn std_io() {
+    test_std_module("io");
+}